@@ -55,6 +55,10 @@ impl SpriteBatch {
         &self.quads[0..self.n_quads]
     }
 
+    pub fn pushed_quads_mut(&mut self) -> &mut [QuadData] {
+        &mut self.quads[0..self.n_quads]
+    }
+
     /// Called after flushing
     pub fn clear(&mut self) {
         self.n_quads = 0;
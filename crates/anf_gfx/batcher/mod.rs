@@ -7,7 +7,7 @@ pub mod bufspecs;
 
 use {
     fna3d_hie::{Pipeline, Shader},
-    fna3h::{buf::SetDataOptions, draw::PrimitiveType, tex::Texture, Device},
+    fna3h::{buf::SetDataOptions, draw::PrimitiveType, tex::Texture, Color, Device},
 };
 
 use crate::{
@@ -18,6 +18,17 @@ use crate::{
     geom3d::Mat4x4,
 };
 
+/// Multiplies every component of the color by `alpha` (for pre-multiplied alpha blending)
+pub fn premultiply(color: Color, alpha: f32) -> Color {
+    let c = color.to_vec4();
+    Color::rgba(
+        (c.x * alpha * 255.0) as u8,
+        (c.y * alpha * 255.0) as u8,
+        (c.z * alpha * 255.0) as u8,
+        (c.w * alpha * 255.0) as u8,
+    )
+}
+
 /// Push quads and flush
 #[derive(Debug)]
 pub struct Batcher {
@@ -28,6 +39,8 @@ pub struct Batcher {
     /// Transformation matrix
     mv: Mat4x4,
     mvp: Mat4x4,
+    /// Multiplier of vertex colors applied on flush
+    opacity: f32,
 }
 
 impl Batcher {
@@ -38,9 +51,41 @@ impl Batcher {
             p: Mat4x4::orthographic(0.0, 0.0, 1.0, 0.0),
             mv: Mat4x4::identity(),
             mvp: Mat4x4::default(),
+            opacity: 1.0,
         }
     }
 
+    /// Sets the projection matrix for the target size in pixels. Flush before calling it
+    pub fn set_screen_size(&mut self, w: u32, h: u32) {
+        self.p = Mat4x4::orthographic_off_center(0.0, w as f32, h as f32, 0.0, 1.0, 0.0);
+    }
+
+    /// Model-view matrix applied before the projection matrix
+    pub fn transform(&self) -> &Mat4x4 {
+        &self.mv
+    }
+
+    /// Sets the model-view matrix (e.g. `Camera2d::view_matrix`). Flush before calling it
+    pub fn set_transform(&mut self, mv: Mat4x4) {
+        self.mv = mv;
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.opacity
+    }
+
+    /// Sets the multiplier of vertex colors (colors are in pre-multiplied alpha)
+    ///
+    /// It's applied on flush, so flush before changing it.
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.opacity = opacity.max(0.0).min(1.0);
+    }
+
+    /// Flush the batcher before pushing the next quad if this is true
+    pub fn is_satured(&self) -> bool {
+        self.batch.is_satured()
+    }
+
     pub fn next_quad_mut<'a>(
         &'a mut self,
         texture: *mut Texture,
@@ -60,7 +105,10 @@ impl Batcher {
             return;
         }
 
-        // FIXME: get viewport
+        if self.opacity < 1.0 {
+            self.apply_opacity();
+        }
+
         self.set_proj_mat(&mut pipe.shader);
         pipe.shader.apply_effect(device, 0);
 
@@ -100,8 +148,16 @@ impl Batcher {
 }
 
 impl Batcher {
+    fn apply_opacity(&mut self) {
+        let opacity = self.opacity;
+        for quad in self.batch.pushed_quads_mut() {
+            for vert in quad.iter_mut() {
+                vert.color = self::premultiply(vert.color, opacity);
+            }
+        }
+    }
+
     fn set_proj_mat(&mut self, shader: &mut Shader) {
-        self.p = Mat4x4::orthographic_off_center(0.0, 1280.0, 720.0, 0.0, 1.0, 0.0);
         self.mvp = Mat4x4::multiply(&self.mv, &self.p);
         unsafe {
             shader.set_param("MatrixTransform", &self.mvp.transpose());
@@ -0,0 +1,165 @@
+//! 2D camera
+//!
+//! [`Camera2d`] maps world coordinates to screen coordinates (in pixels of the render target or
+//! the logical screen size):
+//!
+//! ```text
+//! screen = rotate(world - position, -rot) * zoom + origin * size
+//! ```
+
+use crate::{
+    geom2d::{Rect2f, Vec2f},
+    geom3d::Mat4x4,
+};
+
+/// 2D camera with position, zoom, rotation and origin
+#[derive(Debug, Clone, PartialEq)]
+pub struct Camera2d {
+    /// World position shown at the origin of the screen
+    pub pos: Vec2f,
+    /// Screen pixels per world unit
+    pub zoom: f32,
+    /// Rotation in radians
+    pub rot: f32,
+    /// Normalized screen position where `pos` is shown (`[0.5, 0.5]` is the center)
+    pub origin: Vec2f,
+    /// Screen size in pixels
+    pub size: Vec2f,
+}
+
+impl Camera2d {
+    /// Creates a camera showing `pos` at the center of the screen
+    pub fn new(size: impl Into<Vec2f>) -> Self {
+        Self {
+            pos: Vec2f::zero(),
+            zoom: 1.0,
+            rot: 0.0,
+            origin: Vec2f::new(0.5, 0.5),
+            size: size.into(),
+        }
+    }
+
+    /// Origin in screen pixels
+    pub fn origin_px(&self) -> Vec2f {
+        self.origin * self.size
+    }
+
+    /// Model-view matrix to be given to the batcher (row-major)
+    pub fn view_matrix(&self) -> Mat4x4 {
+        let (sin, cos) = self.rot.sin_cos();
+        let (zc, zs) = (self.zoom * cos, self.zoom * sin);
+        let (p, o) = (self.pos, self.origin_px());
+
+        Mat4x4::new(
+            zc,
+            -zs,
+            0.0,
+            0.0,
+            //
+            zs,
+            zc,
+            0.0,
+            0.0,
+            //
+            0.0,
+            0.0,
+            1.0,
+            0.0,
+            //
+            -p.x * zc - p.y * zs + o.x,
+            p.x * zs - p.y * zc + o.y,
+            0.0,
+            1.0,
+        )
+    }
+
+    pub fn world_to_screen(&self, pos: impl Into<Vec2f>) -> Vec2f {
+        let d = pos.into() - self.pos;
+        self::rotate(d, -self.rot) * self.zoom + self.origin_px()
+    }
+
+    pub fn screen_to_world(&self, pos: impl Into<Vec2f>) -> Vec2f {
+        let d = (pos.into() - self.origin_px()) / self.zoom;
+        self::rotate(d, self.rot) + self.pos
+    }
+
+    /// Bounding box of the world area shown on the screen
+    pub fn visible_rect(&self) -> Rect2f {
+        let corners = [
+            self.screen_to_world([0.0, 0.0]),
+            self.screen_to_world([self.size.x, 0.0]),
+            self.screen_to_world([0.0, self.size.y]),
+            self.screen_to_world([self.size.x, self.size.y]),
+        ];
+
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for c in &corners[1..] {
+            min = Vec2f::new(min.x.min(c.x), min.y.min(c.y));
+            max = Vec2f::new(max.x.max(c.x), max.y.max(c.y));
+        }
+
+        Rect2f::new(min.x, min.y, max.x - min.x, max.y - min.y)
+    }
+}
+
+/// Rotates a row vector counterclockwise (clockwise on y-down screens)
+fn rotate(v: Vec2f, rad: f32) -> Vec2f {
+    let (sin, cos) = rad.sin_cos();
+    Vec2f::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_near(a: Vec2f, b: Vec2f) {
+        assert!(
+            (a.x - b.x).abs() < 1e-3 && (a.y - b.y).abs() < 1e-3,
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    fn camera() -> Camera2d {
+        let mut cam = Camera2d::new([320.0, 180.0]);
+        cam.pos = Vec2f::new(100.0, 50.0);
+        cam.zoom = 2.0;
+        cam.rot = 0.7;
+        cam
+    }
+
+    #[test]
+    fn test_conversion_roundtrip() {
+        let cam = camera();
+        assert_near(cam.world_to_screen(cam.pos), cam.origin_px());
+
+        let p = Vec2f::new(12.0, -34.0);
+        assert_near(cam.screen_to_world(cam.world_to_screen(p)), p);
+    }
+
+    #[test]
+    fn test_view_matrix() {
+        let cam = camera();
+        let m = cam.view_matrix();
+
+        let p = Vec2f::new(12.0, -34.0);
+        // row vector `[x, y, 0, 1]` times the matrix
+        let transformed = Vec2f::new(
+            p.x * m.m11 + p.y * m.m21 + m.m41,
+            p.x * m.m12 + p.y * m.m22 + m.m42,
+        );
+        assert_near(transformed, cam.world_to_screen(p));
+    }
+
+    #[test]
+    fn test_visible_rect() {
+        let mut cam = Camera2d::new([320.0, 180.0]);
+        cam.pos = Vec2f::new(100.0, 50.0);
+        cam.zoom = 2.0;
+
+        let rect = cam.visible_rect();
+        assert_near(rect.left_up(), Vec2f::new(20.0, 5.0));
+        assert_near(rect.size(), Vec2f::new(160.0, 90.0));
+    }
+}
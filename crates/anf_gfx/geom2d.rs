@@ -359,6 +359,15 @@ impl Rect2f {
             || self.bottom() < other.top()
             || other.bottom() < self.top())
     }
+
+    /// Overlapping area. The size is zero if they don't intersect
+    pub fn intersection(&self, other: &Rect2f) -> Rect2f {
+        let x = self.left().max(other.left());
+        let y = self.top().max(other.top());
+        let w = (self.right().min(other.right()) - x).max(0.0);
+        let h = (self.bottom().min(other.bottom()) - y).max(0.0);
+        Rect2f::new(x, y, w, h)
+    }
 }
 
 /// ([x, y], [w, h]) -> Rect2f
@@ -706,3 +715,37 @@ impl Mat2f {
         self.m32 = 0.0;
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intersection() {
+        let outer = Rect2f::new(0.0, 0.0, 100.0, 100.0);
+
+        // nested
+        let inner = Rect2f::new(10.0, 20.0, 30.0, 40.0);
+        assert_eq!(outer.intersection(&inner), inner);
+        assert_eq!(inner.intersection(&outer), inner);
+
+        // overlapping
+        let other = Rect2f::new(50.0, 50.0, 100.0, 100.0);
+        assert_eq!(
+            outer.intersection(&other),
+            Rect2f::new(50.0, 50.0, 50.0, 50.0)
+        );
+
+        // disjoint
+        let far = Rect2f::new(200.0, 300.0, 10.0, 10.0);
+        let rect = outer.intersection(&far);
+        assert_eq!([rect.w, rect.h], [0.0, 0.0]);
+
+        // touching
+        let next = Rect2f::new(100.0, 0.0, 50.0, 100.0);
+        assert_eq!(
+            outer.intersection(&next),
+            Rect2f::new(100.0, 0.0, 0.0, 100.0)
+        );
+    }
+}
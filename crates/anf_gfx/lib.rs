@@ -13,6 +13,7 @@ transposed.
 */
 
 pub mod batcher;
+pub mod camera;
 pub mod cmd;
 pub mod geom2d;
 pub mod geom3d;
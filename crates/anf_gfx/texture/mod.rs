@@ -3,11 +3,13 @@
 //! TODO: remove conversion methods
 
 mod sprite;
+mod target;
 mod texture;
 
 pub use self::{
     sprite::{SpriteData, SubTextureData2d},
-    texture::{Texture2dDrop, TextureData2d, TextureKind},
+    target::RenderTarget2d,
+    texture::{encode_png, Texture2dDrop, TextureData2d, TextureKind},
 };
 
 use crate::cmd::traits::*;
@@ -37,6 +39,27 @@ impl SubTexture2d for TextureData2d {
     }
 }
 
+// RenderTarget2d (delegated to `TextureData2d`)
+impl Texture2d for RenderTarget2d {
+    fn raw_texture(&self) -> *mut Texture {
+        self.raw()
+    }
+
+    fn w(&self) -> f32 {
+        self.texture().w()
+    }
+
+    fn h(&self) -> f32 {
+        self.texture().h()
+    }
+}
+
+impl SubTexture2d for RenderTarget2d {
+    fn uv_rect(&self) -> [f32; 4] {
+        [0.0, 0.0, 1.0, 1.0]
+    }
+}
+
 // SubTexuteData2d (delegated to `Texture2d`)
 impl Texture2d for SubTextureData2d {
     fn raw_texture(&self) -> *mut Texture {
@@ -143,6 +166,18 @@ impl OnSpritePush for TextureData2d {
     }
 }
 
+impl OnSpritePush for RenderTarget2d {
+    fn to_texture(&self) -> TextureData2d {
+        self.texture().clone()
+    }
+
+    fn on_sprite_push(&self, builder: &mut impl QuadParamsBuilder) {
+        builder
+            .src_rect_uv(self.uv_rect())
+            .dest_size_px([self.w(), self.h()]);
+    }
+}
+
 impl OnSpritePush for SubTextureData2d {
     fn to_texture(&self) -> TextureData2d {
         self.texture.clone()
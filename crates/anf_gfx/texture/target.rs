@@ -0,0 +1,76 @@
+//! [`RenderTarget2d`]
+
+use fna3h::{draw::pass::RenderTargetBinding, tex::Texture, Device, SurfaceFormat};
+
+use crate::texture::{TextureData2d, TextureKind};
+
+/// `FNA3D_RenderTargetType` of 2D render targets
+const RENDER_TARGET_TYPE_2D: u8 = 0;
+
+/// Reference counted 2D render target
+///
+/// Draw into it and then draw it as a texture.
+#[derive(Debug, PartialEq, Clone)]
+pub struct RenderTarget2d {
+    texture: TextureData2d,
+}
+
+impl RenderTarget2d {
+    pub fn new(device: &Device, w: u32, h: u32) -> Self {
+        Self::with_format(device, w, h, SurfaceFormat::Color)
+    }
+
+    pub fn with_format(device: &Device, w: u32, h: u32, fmt: SurfaceFormat) -> Self {
+        Self {
+            texture: TextureData2d::new(device, w, h, fmt, TextureKind::RenderTarget),
+        }
+    }
+
+    pub fn texture(&self) -> &TextureData2d {
+        &self.texture
+    }
+
+    pub fn raw(&self) -> *mut Texture {
+        self.texture.raw()
+    }
+
+    /// Size in pixels
+    pub fn size_px(&self) -> [u32; 2] {
+        self.texture.size_px()
+    }
+
+    /// Sets this texture as the render target. Flush before calling it
+    pub fn bind(&self, device: &Device) {
+        let [w, h] = self.size_px();
+
+        let mut binding: RenderTargetBinding = unsafe { std::mem::zeroed() };
+        binding.type_ = RENDER_TARGET_TYPE_2D;
+        unsafe {
+            binding.__bindgen_anon_1.twod.width = w as i32;
+            binding.__bindgen_anon_1.twod.height = h as i32;
+        }
+        binding.levelCount = 1;
+        binding.multiSampleCount = 0;
+        binding.texture = self.raw();
+        binding.colorBuffer = std::ptr::null_mut();
+
+        device.set_render_targets(
+            &mut binding,
+            1,
+            std::ptr::null_mut(),
+            fna3h::DepthFormat::None,
+            false,
+        );
+    }
+
+    /// Sets the backbuffer as the render target. Flush before calling it
+    pub fn unbind(device: &Device) {
+        device.set_render_targets(
+            std::ptr::null_mut(),
+            0,
+            std::ptr::null_mut(),
+            fna3h::DepthFormat::None,
+            false,
+        );
+    }
+}
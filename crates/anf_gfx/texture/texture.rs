@@ -1,8 +1,9 @@
 //! [`TextureData2d`]
 
 use std::{
-    fs::File,
-    io::{BufReader, Read, Seek},
+    fs::{self, File},
+    io::{self, BufReader, Read, Seek},
+    path::Path,
     rc::Rc,
 };
 
@@ -43,7 +44,7 @@ impl Drop for Texture2dDrop {
 
 impl Texture2dDrop {
     pub fn new(device: &Device, w: u32, h: u32, fmt: SurfaceFormat, kind: TextureKind) -> Self {
-        let fmt = self::get_init_format(fmt, TextureKind::Texture);
+        let fmt = self::get_init_format(fmt, kind);
         let raw = device.create_texture_2d(fmt, w, h, 1, kind == TextureKind::RenderTarget);
 
         Texture2dDrop {
@@ -140,13 +141,51 @@ impl Texture2dDrop {
         device.set_texture_data_2d(self.raw, x, y, w, h, target_mipmap_level, data);
     }
 
-    // /// VERY HEAVY task
-    // pub fn save_to_png(&self, path: &Path) {
-    //     let pixels = Vec::with_capacity(32 * self.w as usize * self.h as usize);
-    //     self.device
-    //         .get_texture_data_2d(self.raw, self.x, self.y, self.w, self.h, 0, &mut pixels);
-    //     // TODO:
-    // }
+    /// Reads RGBA8 pixels back from the GPU. VERY HEAVY task
+    ///
+    /// Only `SurfaceFormat::Color` textures (including render targets) are supported. Other
+    /// formats return an `InvalidInput` error.
+    pub fn get_pixels(&self) -> io::Result<Vec<u8>> {
+        if self.fmt != SurfaceFormat::Color {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "only `SurfaceFormat::Color` textures can be read back (got `{:?}`)",
+                    self.fmt
+                ),
+            ));
+        }
+
+        let mut pixels = vec![0; 4 * self.w as usize * self.h as usize];
+        self.device
+            .get_texture_data_2d(self.raw, 0, 0, self.w, self.h, 0, &mut pixels);
+        Ok(pixels)
+    }
+
+    /// Reads pixels back from the GPU and encodes them as PNG. VERY HEAVY task
+    pub fn encode_png(&self) -> io::Result<Vec<u8>> {
+        self::encode_png(&self.get_pixels()?, self.w, self.h)
+    }
+
+    /// VERY HEAVY task
+    pub fn save_to_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.encode_png()?)
+    }
+}
+
+/// Encodes RGBA8 pixels as PNG
+pub fn encode_png(pixels: &[u8], w: u32, h: u32) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+
+    {
+        let mut encoder = png::Encoder::new(&mut bytes, w, h);
+        encoder.set_color(png::ColorType::RGBA);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(pixels)?;
+    }
+
+    Ok(bytes)
 }
 
 fn get_init_format(fmt: SurfaceFormat, kind: TextureKind) -> SurfaceFormat {
@@ -0,0 +1,374 @@
+/*! Camera behaviors on top of [`Camera2d`]
+
+[`CameraController`] follows a target, clamps the camera to world bounds, shakes the screen and
+tweens zoom, advanced with `dt` of `update`:
+
+```no_run
+use anf::{engine::camera::CameraController, gfx::{geom2d::Rect2f, Camera2d}};
+use std::time::Duration;
+
+let mut ctrl = CameraController::new(Camera2d::new([320.0, 180.0]));
+ctrl.bounds = Some(Rect2f::new(0.0, 0.0, 1280.0, 720.0));
+
+// every update
+ctrl.set_target(Some([200.0, 100.0].into()));
+ctrl.update(Duration::from_millis(16));
+
+// on hit
+ctrl.shake.add_trauma(0.5);
+
+// then draw with `dcx.batch_with_camera(&ctrl.camera())`
+```
+!*/
+
+use std::time::Duration;
+
+use crate::gfx::{
+    geom2d::{Rect2f, Vec2f},
+    Camera2d,
+};
+
+/// Smooth follow settings
+#[derive(Debug, Clone, PartialEq)]
+pub struct Follow {
+    /// Half size of the area around the camera position where the target can move without
+    /// moving the camera (in world units)
+    pub dead_zone: Vec2f,
+    /// How fast the camera catches up (per second). `0.0` snaps to the target
+    pub smoothing: f32,
+    /// Seconds of target velocity to look ahead
+    pub look_ahead: f32,
+}
+
+impl Default for Follow {
+    fn default() -> Self {
+        Self {
+            dead_zone: Vec2f::zero(),
+            smoothing: 8.0,
+            look_ahead: 0.0,
+        }
+    }
+}
+
+/// Trauma-based screen shake
+///
+/// Shake strength is `trauma^2`, so small trauma barely shakes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shake {
+    /// Max offset in world units
+    pub max_offset: f32,
+    /// Max rotation in radians
+    pub max_rot: f32,
+    /// Speed of the noise
+    pub frequency: f32,
+    /// Trauma lost per second
+    pub decay: f32,
+    trauma: f32,
+}
+
+impl Default for Shake {
+    fn default() -> Self {
+        Self {
+            max_offset: 8.0,
+            max_rot: 0.05,
+            frequency: 20.0,
+            decay: 1.5,
+            trauma: 0.0,
+        }
+    }
+}
+
+impl Shake {
+    pub fn trauma(&self) -> f32 {
+        self.trauma
+    }
+
+    /// Adds trauma, saturating in `[0.0, 1.0]`
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).max(0.0).min(1.0);
+    }
+
+    fn update(&mut self, dt: f32) {
+        self.trauma = (self.trauma - self.decay * dt).max(0.0);
+    }
+
+    /// `(offset, rot)` at time `t`
+    fn sample(&self, t: f32) -> (Vec2f, f32) {
+        let amount = self.trauma * self.trauma;
+        if amount == 0.0 {
+            return (Vec2f::zero(), 0.0);
+        }
+
+        let t = t * self.frequency;
+        let offset =
+            Vec2f::new(self::noise(t, 0.0), self::noise(t, 13.7)) * (self.max_offset * amount);
+        let rot = self::noise(t, 31.1) * self.max_rot * amount;
+        (offset, rot)
+    }
+}
+
+/// Smooth pseudo-random value in `[-1.0, 1.0]` (sum of sines with incommensurate frequencies)
+fn noise(t: f32, seed: f32) -> f32 {
+    (t + seed).sin() * 0.5 + (t * 2.3 + seed * 1.7).sin() * 0.3 + (t * 4.1 + seed * 3.1).sin() * 0.2
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct ZoomTween {
+    from: f32,
+    to: f32,
+    elapsed: f32,
+    duration: f32,
+}
+
+impl ZoomTween {
+    /// Zoom with smoothstep easing
+    fn value(&self) -> f32 {
+        let t = (self.elapsed / self.duration).min(1.0);
+        let t = t * t * (3.0 - 2.0 * t);
+        self.from + (self.to - self.from) * t
+    }
+
+    fn is_finished(&self) -> bool {
+        self.elapsed >= self.duration
+    }
+}
+
+/// Follow, bounds clamping, screen shake and zoom tween
+#[derive(Debug, Clone)]
+pub struct CameraController {
+    /// Camera without shake
+    base: Camera2d,
+    pub follow: Follow,
+    /// World area the camera must not show outside of
+    pub bounds: Option<Rect2f>,
+    pub shake: Shake,
+    target: Option<Vec2f>,
+    prev_target: Option<Vec2f>,
+    zoom_tween: Option<ZoomTween>,
+    /// Seconds since created (for shake noise)
+    time: f32,
+}
+
+impl CameraController {
+    pub fn new(camera: Camera2d) -> Self {
+        Self {
+            base: camera,
+            follow: Follow::default(),
+            bounds: None,
+            shake: Shake::default(),
+            target: None,
+            prev_target: None,
+            zoom_tween: None,
+            time: 0.0,
+        }
+    }
+
+    /// Camera with shake applied. Draw with this
+    pub fn camera(&self) -> Camera2d {
+        let (offset, rot) = self.shake.sample(self.time);
+        let mut cam = self.base.clone();
+        cam.pos += offset;
+        cam.rot += rot;
+        cam
+    }
+
+    /// Camera without shake
+    pub fn base_camera(&self) -> &Camera2d {
+        &self.base
+    }
+
+    pub fn base_camera_mut(&mut self) -> &mut Camera2d {
+        &mut self.base
+    }
+
+    /// Sets the position to follow. `None` stops following
+    pub fn set_target(&mut self, target: Option<Vec2f>) {
+        if target.is_none() {
+            self.prev_target = None;
+        }
+        self.target = target;
+    }
+
+    /// Moves the camera to the target immediately (e.g. on scene change)
+    pub fn snap_to_target(&mut self) {
+        if let Some(target) = self.target {
+            self.base.pos = target;
+            self.prev_target = Some(target);
+            self.clamp_to_bounds();
+        }
+    }
+
+    /// Tweens zoom over `duration`. Zero duration sets the zoom immediately
+    pub fn zoom_to(&mut self, zoom: f32, duration: Duration) {
+        if duration == Duration::new(0, 0) {
+            self.base.zoom = zoom;
+            self.zoom_tween = None;
+            return;
+        }
+
+        self.zoom_tween = Some(ZoomTween {
+            from: self.base.zoom,
+            to: zoom,
+            elapsed: 0.0,
+            duration: duration.as_secs_f32(),
+        });
+    }
+
+    pub fn update(&mut self, dt: Duration) {
+        let dt = dt.as_secs_f32();
+        self.time += dt;
+
+        self.update_zoom(dt);
+        self.update_follow(dt);
+        self.clamp_to_bounds();
+        self.shake.update(dt);
+    }
+}
+
+/// Internals
+impl CameraController {
+    fn update_zoom(&mut self, dt: f32) {
+        if let Some(tween) = self.zoom_tween.as_mut() {
+            tween.elapsed += dt;
+            self.base.zoom = tween.value();
+            if tween.is_finished() {
+                self.zoom_tween = None;
+            }
+        }
+    }
+
+    fn update_follow(&mut self, dt: f32) {
+        let target = match self.target {
+            Some(target) => target,
+            None => return,
+        };
+
+        let velocity = match self.prev_target {
+            Some(prev) if dt > 0.0 => (target - prev) / dt,
+            _ => Vec2f::zero(),
+        };
+        self.prev_target = Some(target);
+
+        let desired = target + velocity * self.follow.look_ahead;
+
+        // move only so much that the desired position enters the dead zone
+        let pos = self.base.pos;
+        let dz = self.follow.dead_zone;
+        let goal = Vec2f::new(
+            self::exit_dead_zone(pos.x, desired.x, dz.x),
+            self::exit_dead_zone(pos.y, desired.y, dz.y),
+        );
+
+        let t = if self.follow.smoothing <= 0.0 {
+            1.0
+        } else {
+            1.0 - (-self.follow.smoothing * dt).exp()
+        };
+        self.base.pos = pos + (goal - pos) * t;
+    }
+
+    /// Shifts the camera so that the visible area is in the bounds (centered if it's larger than
+    /// the bounds)
+    fn clamp_to_bounds(&mut self) {
+        let bounds = match self.bounds.as_ref() {
+            Some(bounds) => bounds.clone(),
+            None => return,
+        };
+
+        let visible = self.base.visible_rect();
+        let mut clamped = visible.clone();
+
+        if clamped.w > bounds.w {
+            clamped.x = bounds.x + (bounds.w - clamped.w) / 2.0;
+        } else {
+            clamped.clamp_x(bounds.left(), bounds.right());
+        }
+
+        if clamped.h > bounds.h {
+            clamped.y = bounds.y + (bounds.h - clamped.h) / 2.0;
+        } else {
+            clamped.clamp_y(bounds.top(), bounds.bottom());
+        }
+
+        self.base.pos += clamped.left_up() - visible.left_up();
+    }
+}
+
+/// Position on an axis that keeps `desired` on the edge of the dead zone (or `pos` if it's in)
+fn exit_dead_zone(pos: f32, desired: f32, half: f32) -> f32 {
+    let d = desired - pos;
+    if d > half {
+        desired - half
+    } else if d < -half {
+        desired + half
+    } else {
+        pos
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn ctrl() -> CameraController {
+        let mut ctrl = CameraController::new(Camera2d::new([320.0, 180.0]));
+        ctrl.follow.smoothing = 0.0;
+        ctrl
+    }
+
+    #[test]
+    fn test_dead_zone() {
+        let mut ctrl = ctrl();
+        ctrl.follow.dead_zone = Vec2f::new(10.0, 10.0);
+
+        ctrl.set_target(Some(Vec2f::new(5.0, -5.0)));
+        ctrl.update(Duration::from_millis(16));
+        assert_eq!(ctrl.base_camera().pos, Vec2f::zero());
+
+        ctrl.set_target(Some(Vec2f::new(30.0, -5.0)));
+        ctrl.update(Duration::from_millis(16));
+        assert_eq!(ctrl.base_camera().pos, Vec2f::new(20.0, 0.0));
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut ctrl = ctrl();
+        ctrl.bounds = Some(Rect2f::new(0.0, 0.0, 1000.0, 100.0));
+
+        ctrl.set_target(Some(Vec2f::new(10.0, 10.0)));
+        ctrl.update(Duration::from_millis(16));
+
+        // left edge is clamped and the too small height is centered
+        let visible = ctrl.base_camera().visible_rect();
+        assert_eq!(visible.left(), 0.0);
+        assert_eq!(ctrl.base_camera().pos.y, 50.0);
+    }
+
+    #[test]
+    fn test_shake_decays() {
+        let mut ctrl = ctrl();
+        ctrl.shake.add_trauma(2.0);
+        assert_eq!(ctrl.shake.trauma(), 1.0);
+
+        ctrl.update(Duration::from_millis(100));
+        assert!(ctrl.shake.trauma() < 1.0);
+        assert_ne!(ctrl.camera().pos, ctrl.base_camera().pos);
+
+        ctrl.update(Duration::from_secs(1));
+        assert_eq!(ctrl.shake.trauma(), 0.0);
+        assert_eq!(ctrl.camera(), ctrl.base_camera().clone());
+    }
+
+    #[test]
+    fn test_zoom_tween() {
+        let mut ctrl = ctrl();
+        ctrl.zoom_to(3.0, Duration::from_millis(100));
+
+        ctrl.update(Duration::from_millis(50));
+        let zoom = ctrl.base_camera().zoom;
+        assert!(1.0 < zoom && zoom < 3.0);
+
+        ctrl.update(Duration::from_millis(50));
+        assert_eq!(ctrl.base_camera().zoom, 3.0);
+    }
+}
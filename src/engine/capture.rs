@@ -0,0 +1,495 @@
+/*! Frame recording to PNG sequences and animated GIFs (enabled with `capture` feature)
+
+[`FrameRecorder`] keeps frames with their display time, measured in real time (so pause and
+slow motion are recorded as they are seen):
+
+```no_run
+use anf::engine::{capture::FrameRecorder, prelude::*};
+use std::time::Duration;
+
+struct Game {
+    dcx: DrawContext,
+    recorder: FrameRecorder,
+}
+
+impl AnfLifecycle for Game {
+    fn render(&mut self, _dt: Duration, _alpha: f32) -> AnfResult<()> {
+        // ~ draw everything
+        self.recorder.capture(&mut self.dcx);
+        Ok(())
+    }
+}
+
+// keep the last 10 seconds, capturing every other frame
+let mut recorder = FrameRecorder::ring(Duration::from_secs(10));
+recorder.set_interval(2);
+// ~ on "clip" key
+recorder.save_gif("clip.gif").unwrap();
+```
+
+Capturing reads the backbuffer back from the GPU and it's VERY HEAVY. Frames are kept
+uncompressed (`4 * w * h` bytes each, about 3.5 MiB at 720p and 7.9 MiB at 1080p), so use
+[`FrameRecorder::set_interval`] and small windows for long recordings. Ring mode keeps at most
+[`FrameRecorder::max_ring_bytes`] of pixels (512 MiB by default, about 145 frames at 720p and 64
+frames at 1080p) and drops older frames even if they're in the duration.
+
+GIF delays are in centiseconds and browsers slow down delays under 2 cs, so frames shorter than
+that are merged into the next frame when encoding.
+
+Without a window (e.g. [`HeadlessRunner`]), push images yourself with
+[`FrameRecorder::push_frame`] (e.g. pixels of a render target).
+
+[`HeadlessRunner`]: crate::engine::core::headless::HeadlessRunner
+!*/
+
+use std::{
+    collections::VecDeque,
+    fs, io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::engine::{
+    core::{
+        clock::{RealTime, TimeSource},
+        window::RgbaImage,
+    },
+    draw::DrawContext,
+};
+
+/// Display time of the first captured frame, which has no previous frame to measure from
+const FIRST_FRAME_DELAY: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Default upper limit of pixel bytes kept in ring mode
+const DEFAULT_MAX_RING_BYTES: usize = 512 * 1024 * 1024;
+
+/// Minimum GIF delay in centiseconds (browsers play shorter delays as 10 cs)
+const MIN_GIF_DELAY: u16 = 2;
+
+/// Captured frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedFrame {
+    pub image: RgbaImage,
+    /// How long the frame is displayed
+    pub delay: Duration,
+}
+
+/// What frames are kept
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Not recording
+    Stopped,
+    /// Records until stopped or until the number of frames are captured
+    Range { n_remaining: Option<usize> },
+    /// Always recording, keeping frames of the last duration (up to `max_ring_bytes`)
+    Ring { duration: Duration },
+}
+
+/// Records frames for PNG sequences and animated GIFs
+#[derive(Debug)]
+pub struct FrameRecorder {
+    mode: Mode,
+    frames: VecDeque<RecordedFrame>,
+    /// Capture every `interval` frames
+    interval: usize,
+    /// Number of frames since the last capture
+    n_skipped: usize,
+    /// Display time of the skipped frames, added to the next captured frame
+    skipped_delay: Duration,
+    /// [Ring] Upper limit of the pixel bytes of kept frames
+    max_ring_bytes: usize,
+    /// Pixel bytes of the recorded frames
+    n_bytes: usize,
+    /// Measures frame time for [`FrameRecorder::capture`]
+    time_source: Box<dyn TimeSource>,
+    /// Time of the last [`FrameRecorder::capture`] call
+    last_capture: Option<Duration>,
+}
+
+impl Default for FrameRecorder {
+    fn default() -> Self {
+        Self {
+            mode: Mode::Stopped,
+            frames: VecDeque::new(),
+            interval: 1,
+            n_skipped: 0,
+            skipped_delay: Duration::new(0, 0),
+            max_ring_bytes: DEFAULT_MAX_RING_BYTES,
+            n_bytes: 0,
+            time_source: Box::new(RealTime::new()),
+            last_capture: None,
+        }
+    }
+}
+
+impl FrameRecorder {
+    /// Creates a stopped recorder. Call [`FrameRecorder::start`] to record
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a recorder that always keeps the last `duration` of frames
+    pub fn ring(duration: Duration) -> Self {
+        Self {
+            mode: Mode::Ring { duration },
+            ..Self::default()
+        }
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.mode != Mode::Stopped
+    }
+
+    /// Clears recorded frames and records `n_frames` (or until [`FrameRecorder::stop`] is called)
+    ///
+    /// `Some(0)` stops recording.
+    pub fn start(&mut self, n_frames: Option<usize>) {
+        self.clear();
+        self.mode = match n_frames {
+            Some(0) => Mode::Stopped,
+            n_remaining => Mode::Range { n_remaining },
+        };
+    }
+
+    /// Stops recording, keeping recorded frames
+    pub fn stop(&mut self) {
+        self.mode = Mode::Stopped;
+    }
+
+    /// Captures every `n`th frame (`1` by default). Delays of skipped frames are added to the
+    /// captured frames
+    pub fn set_interval(&mut self, n: usize) {
+        assert!(n > 0, "capture interval must be positive");
+        self.interval = n;
+    }
+
+    pub fn max_ring_bytes(&self) -> usize {
+        self.max_ring_bytes
+    }
+
+    /// [Ring] Sets the upper limit of the pixel bytes of kept frames (512 MiB by default), whatever
+    /// their display time is. The newest frame is always kept
+    pub fn set_max_ring_bytes(&mut self, n_bytes: usize) {
+        self.max_ring_bytes = n_bytes;
+    }
+
+    /// Measures frame time of [`FrameRecorder::capture`] with the time source (real time by
+    /// default)
+    pub fn set_time_source(&mut self, time_source: impl TimeSource + 'static) {
+        self.time_source = Box::new(time_source);
+        self.last_capture = None;
+    }
+
+    pub fn frames(&self) -> impl Iterator<Item = &RecordedFrame> {
+        self.frames.iter()
+    }
+
+    pub fn n_frames(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// Pixel bytes of the recorded frames
+    pub fn n_bytes(&self) -> usize {
+        self.n_bytes
+    }
+
+    /// Sum of the delays of the recorded frames
+    pub fn duration(&self) -> Duration {
+        self.frames.iter().map(|f| f.delay).sum()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+        self.n_bytes = 0;
+        self.n_skipped = 0;
+        self.skipped_delay = Duration::new(0, 0);
+    }
+}
+
+/// Recording
+impl FrameRecorder {
+    /// Captures the backbuffer if recording. Call it at the end of every `render`
+    ///
+    /// The display time is the real time since the last call (not the game time `dt`, which is
+    /// zero while paused).
+    pub fn capture(&mut self, dcx: &mut DrawContext) {
+        let dt = self.measure_frame_time();
+        if self.should_capture(dt) {
+            let image = dcx.read_backbuffer();
+            self.add_frame(image);
+        }
+    }
+
+    /// Adds an image as a frame if recording (for headless or offscreen rendering)
+    ///
+    /// `dt` is the display time of the frame.
+    pub fn push_frame(&mut self, image: RgbaImage, dt: Duration) {
+        if self.should_capture(dt) {
+            self.add_frame(image);
+        }
+    }
+
+    fn measure_frame_time(&mut self) -> Duration {
+        let now = self.time_source.now();
+        let dt = match self.last_capture {
+            Some(last) => now - last,
+            None => FIRST_FRAME_DELAY,
+        };
+        self.last_capture = Some(now);
+        dt
+    }
+
+    /// Counts the frame and returns if it should be captured
+    fn should_capture(&mut self, dt: Duration) -> bool {
+        if !self.is_recording() {
+            return false;
+        }
+
+        self.skipped_delay += dt;
+        self.n_skipped += 1;
+        self.n_skipped >= self.interval
+    }
+
+    fn add_frame(&mut self, image: RgbaImage) {
+        let delay = self.skipped_delay;
+        self.n_skipped = 0;
+        self.skipped_delay = Duration::new(0, 0);
+
+        self.n_bytes += image.pixels.len();
+        self.frames.push_back(RecordedFrame { image, delay });
+
+        match &mut self.mode {
+            Mode::Stopped => {}
+            Mode::Range { n_remaining } => {
+                if let Some(n) = n_remaining {
+                    *n -= 1;
+                    if *n == 0 {
+                        self.mode = Mode::Stopped;
+                    }
+                }
+            }
+            Mode::Ring { duration } => {
+                let duration = *duration;
+                // keep the newest frames that fit in the duration and the limit
+                while self.frames.len() > 1
+                    && (self.n_bytes > self.max_ring_bytes || self.duration() > duration)
+                {
+                    if let Some(frame) = self.frames.pop_front() {
+                        self.n_bytes -= frame.image.pixels.len();
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Output
+impl FrameRecorder {
+    /// Saves frames as `frame_0000.png`, `frame_0001.png`, .. in the directory
+    pub fn save_png_sequence(&self, dir: impl AsRef<Path>) -> io::Result<Vec<PathBuf>> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+
+        self.frames
+            .iter()
+            .enumerate()
+            .map(|(i, frame)| {
+                let path = dir.join(format!("frame_{:04}.png", i));
+                frame.image.save_png(&path)?;
+                Ok(path)
+            })
+            .collect()
+    }
+
+    /// Encodes frames as a looping GIF. Every frame must have the same size
+    ///
+    /// Frames shorter than 2 centiseconds are merged into the next frame.
+    pub fn encode_gif(&self) -> io::Result<Vec<u8>> {
+        let (w, h) = match self.frames.front() {
+            Some(frame) => (frame.image.w, frame.image.h),
+            None => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "no frame is recorded",
+                ))
+            }
+        };
+
+        if self.frames.iter().any(|f| [f.image.w, f.image.h] != [w, h]) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "frames of different sizes can't be encoded as a GIF",
+            ));
+        }
+
+        let mut bytes = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut bytes, w as u16, h as u16, &[])
+                .map_err(self::to_io_error)?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(self::to_io_error)?;
+
+            let delays: Vec<_> = self.frames.iter().map(|f| f.delay).collect();
+            for (i, delay) in self::gif_delays(&delays) {
+                let frame = &self.frames[i];
+                let mut pixels = frame.image.pixels.clone();
+                let mut gif_frame =
+                    gif::Frame::from_rgba_speed(w as u16, h as u16, &mut pixels, 10);
+                gif_frame.delay = delay;
+                encoder.write_frame(&gif_frame).map_err(self::to_io_error)?;
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn save_gif(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.encode_gif()?)
+    }
+}
+
+/// Returns `(frame_index, delay_in_centiseconds)` of the frames to encode as a GIF
+///
+/// Frames are skipped until the accumulated delay reaches [`MIN_GIF_DELAY`], and the remainders
+/// are carried to the next frames so that the total playback time matches. The last frame is
+/// always emitted.
+fn gif_delays(delays: &[Duration]) -> Vec<(usize, u16)> {
+    const CS: u128 = 10_000_000; // nanoseconds
+
+    let mut emitted = Vec::new();
+    let mut nanos = 0;
+    for (i, delay) in delays.iter().enumerate() {
+        nanos += delay.as_nanos();
+        let cs = nanos / CS;
+        if cs >= MIN_GIF_DELAY as u128 || i + 1 == delays.len() {
+            nanos %= CS;
+            let cs = cs.max(MIN_GIF_DELAY as u128).min(u16::MAX as u128) as u16;
+            emitted.push((i, cs));
+        }
+    }
+    emitted
+}
+
+fn to_io_error(err: gif::EncodingError) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn image() -> RgbaImage {
+        RgbaImage::new(vec![255; 4 * 2 * 2], 2, 2)
+    }
+
+    #[test]
+    fn test_range() {
+        let mut rec = FrameRecorder::new();
+        rec.push_frame(image(), Duration::from_millis(10));
+        assert_eq!(rec.n_frames(), 0);
+
+        rec.start(Some(3));
+        for _ in 0..5 {
+            rec.push_frame(image(), Duration::from_millis(10));
+        }
+        assert_eq!(rec.n_frames(), 3);
+        assert!(!rec.is_recording());
+    }
+
+    #[test]
+    fn test_ring_with_interval() {
+        let mut rec = FrameRecorder::ring(Duration::from_millis(100));
+        rec.set_interval(2);
+        for _ in 0..100 {
+            rec.push_frame(image(), Duration::from_millis(10));
+        }
+
+        // every other frame is captured with the delay of two frames
+        assert!(rec.frames().all(|f| f.delay == Duration::from_millis(20)));
+        assert_eq!(rec.n_frames(), 5);
+        assert_eq!(rec.duration(), Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_gif() {
+        let mut rec = FrameRecorder::new();
+        rec.start(None);
+        rec.push_frame(image(), Duration::from_millis(20));
+        rec.push_frame(image(), Duration::from_millis(20));
+
+        let bytes = rec.encode_gif().unwrap();
+        assert_eq!(&bytes[0..6], b"GIF89a");
+
+        rec.push_frame(RgbaImage::new(vec![0; 4], 1, 1), Duration::from_millis(20));
+        assert!(rec.encode_gif().is_err());
+    }
+
+    #[test]
+    fn test_start_zero_frames() {
+        let mut rec = FrameRecorder::new();
+        rec.start(Some(0));
+        assert!(!rec.is_recording());
+        rec.push_frame(image(), Duration::from_millis(10));
+        assert_eq!(rec.n_frames(), 0);
+    }
+
+    #[test]
+    fn test_ring_zero_delay() {
+        let mut rec = FrameRecorder::ring(Duration::from_millis(100));
+        rec.set_max_ring_bytes(8 * image().pixels.len());
+        // e.g. game time while paused
+        for _ in 0..100 {
+            rec.push_frame(image(), Duration::new(0, 0));
+        }
+        assert_eq!(rec.n_frames(), 8);
+        assert_eq!(rec.n_bytes(), 8 * image().pixels.len());
+    }
+
+    #[test]
+    fn test_capture_measures_real_time() {
+        use crate::engine::core::clock::ManualTime;
+
+        let mut rec = FrameRecorder::new();
+        let time = ManualTime::new();
+        rec.set_time_source(time.clone());
+
+        assert_eq!(rec.measure_frame_time(), FIRST_FRAME_DELAY);
+        time.advance(Duration::from_millis(33));
+        assert_eq!(rec.measure_frame_time(), Duration::from_millis(33));
+    }
+
+    #[test]
+    fn test_gif_delays() {
+        // 60 FPS: 16 ms frames are merged into 2 or 3 cs frames
+        let delays = gif_delays(&vec![Duration::from_millis(16); 60]);
+        assert!(delays.iter().all(|&(_, d)| d >= MIN_GIF_DELAY));
+        assert_eq!(delays.iter().map(|&(_, d)| d as u64).sum::<u64>(), 96);
+        assert_eq!(delays[0], (1, 3));
+        assert_eq!(delays.last().unwrap().0, 59);
+
+        // long frames are all emitted
+        let delays = gif_delays(&vec![Duration::from_millis(50); 3]);
+        assert_eq!(delays, vec![(0, 5), (1, 5), (2, 5)]);
+
+        // a short last frame is emitted with the minimum delay
+        let delays = gif_delays(&[Duration::from_millis(30), Duration::from_millis(5)]);
+        assert_eq!(delays, vec![(0, 3), (1, MIN_GIF_DELAY)]);
+
+        // playback time matches the recording within one centisecond
+        let mut rec = FrameRecorder::new();
+        rec.start(None);
+        for i in 0..30 {
+            rec.push_frame(image(), Duration::from_micros(16_667 + i * 100));
+        }
+        let delays: Vec<_> = rec.frames().map(|f| f.delay).collect();
+        let delays = gif_delays(&delays);
+        let playback =
+            Duration::from_millis(10) * delays.iter().map(|&(_, d)| d as u32).sum::<u32>();
+        assert!(rec.duration() - playback < Duration::from_millis(10));
+    }
+}
@@ -4,7 +4,7 @@
 
 ```no_run
 use {
-    anf::engine::time::GameClock,
+    anf::engine::core::clock::GameClock,
     sdl2::EventPump,
 };
 
@@ -21,23 +21,158 @@ fn tick_one_frame(
     }
 
     let time_step = clock.timestep_draw();
-    // draw your game
+    let alpha = clock.alpha();
+    // draw your game, interpolating states with `alpha`
 
     // end of the frame
 }
 ```
 !*/
 
-use std::time::{Duration, Instant};
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    fmt,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Time source of [`GameClock`]
+///
+/// Swap it to run the game loop deterministically or faster than real time.
+pub trait TimeSource: fmt::Debug {
+    /// Time passed since some fixed point
+    fn now(&mut self) -> Duration;
+    /// Blocks (or pretends to block) the current thread for the duration
+    fn sleep(&mut self, duration: Duration);
+}
+
+/// [`TimeSource`] backed by [`Instant`] and [`std::thread::sleep`]
+#[derive(Debug, Clone)]
+pub struct RealTime {
+    origin: Instant,
+}
 
-/// TODO: use this
+impl Default for RealTime {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl RealTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl TimeSource for RealTime {
+    fn now(&mut self) -> Duration {
+        self.origin.elapsed()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        // inaccurate but enough for making frames
+        std::thread::sleep(duration);
+    }
+}
+
+/// [`TimeSource`] that only advances when told to
+///
+/// * `sleep` advances the time immediately by exactly the given duration
+/// * `now` advances the time by `step` (zero by default), i.e., every frame takes `step`
+///
+/// Clones share the time, so keep one to advance the time of the clock from outside.
+#[derive(Debug, Clone, Default)]
+pub struct ManualTime {
+    now: Rc<Cell<Duration>>,
+    step: Duration,
+}
+
+impl ManualTime {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a time source where every frame takes `step`
+    pub fn stepped(step: Duration) -> Self {
+        Self {
+            now: Rc::new(Cell::new(Duration::new(0, 0))),
+            step,
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+impl TimeSource for ManualTime {
+    fn now(&mut self) -> Duration {
+        self.advance(self.step);
+        self.now.get()
+    }
+
+    fn sleep(&mut self, duration: Duration) {
+        self.advance(duration);
+    }
+}
+
+/// Frame rate policy of [`GameClock`]
+///
+/// The value is updates (frames) per second.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum TargetFps {
+    /// Fixed timestep: the clock runs `update`s with constant `dt`, catching up if needed
     Fixed(u32),
+    /// Variable timestep: one `update` per frame, capped to the frame rate (`0` for uncapped)
     Variable(u32),
 }
 
+impl Default for TargetFps {
+    fn default() -> Self {
+        TargetFps::Fixed(60)
+    }
+}
+
+impl TargetFps {
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, TargetFps::Fixed(_))
+    }
+
+    /// Duration of one frame, or zero if uncapped
+    pub fn target_elapsed(&self) -> Duration {
+        match *self {
+            TargetFps::Fixed(n) | TargetFps::Variable(n) if n > 0 => {
+                Duration::from_secs_f64(1.0 / n as f64)
+            }
+            _ => Duration::new(0, 0),
+        }
+    }
+}
+
+/// Snapshot of frame timing provided by [`GameClock::timing`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FrameTiming {
+    /// Total game time consumed by `update`s since the clock is created
+    pub total: Duration,
+    /// Number of frames ticked so far, including the current frame
+    pub frame: u64,
+    /// Number of `update`s performed in the current frame
+    pub n_updates: u32,
+    /// [Fixed timestep only] True if the game can't keep up with the target frame rate
+    pub is_slow: bool,
+    /// [Fixed timestep only] Accumulated number of extra catch-up updates
+    pub lag: u32,
+}
+
 /// Creates frames
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct GameClock {
     /// Update/draw timestep duration
     time_step: Duration,
@@ -46,48 +181,99 @@ pub struct GameClock {
     /// Total duration passed since the clock is created
     total: Duration,
     /// Temporary value to accumulate time
-    pub(crate) last_time: Instant,
+    last_time: Duration,
+    time_source: Box<dyn TimeSource>,
     /// [Fixed timestep only]
     ///
     /// The value is incremented by `n_updates - 1` on every tick
     lag: u32,
-    /// If the lag is too big, this is true (though the value is not provided to user for now)
+    /// If the lag is too big, this is true
     is_slow: bool,
+    /// Number of ticks
+    n_frames: u64,
+    /// Number of updates in the current frame
+    n_updates: u32,
     /// Configuration
-    is_fixed_timestep: bool,
-    updates_per_sec: f64,
+    target_fps: TargetFps,
+    /// [Fixed timestep only] Maximum number of catch-up updates in one frame
+    max_updates_per_frame: u32,
+    /// If true, time doesn't advance (except for single-frame steps)
+    is_paused: bool,
+    /// Number of requested single-frame steps
+    n_steps: u32,
+    /// Multiplier of elapsed time
+    time_scale: f64,
+    /// Upper limit of elapsed time in one frame
+    max_elapsed: Duration,
+    /// [Variable timestep only] Number of frames to average `dt` over
+    n_smoothing: usize,
+    /// [Variable timestep only] Recent deltas used for smoothing
+    recent_deltas: VecDeque<Duration>,
 }
 
 impl GameClock {
     pub fn new() -> Self {
-        Self {
+        Self::with_target_fps(TargetFps::default())
+    }
+
+    pub fn with_target_fps(target_fps: TargetFps) -> Self {
+        Self::with_time_source(target_fps, RealTime::new())
+    }
+
+    /// Creates a clock driven by any [`TimeSource`] (e.g. [`ManualTime`] for tests)
+    pub fn with_time_source(target_fps: TargetFps, time_source: impl TimeSource + 'static) -> Self {
+        let mut time_source = Box::new(time_source);
+        let mut clock = Self {
             time_step: Duration::new(0, 0),
             accum: Duration::new(0, 0),
             total: Duration::new(0, 0),
-            last_time: Instant::now(),
+            last_time: time_source.now(),
+            time_source,
             lag: 0,
             is_slow: false,
-            is_fixed_timestep: true,
-            updates_per_sec: 60.0,
-        }
+            n_frames: 0,
+            n_updates: 0,
+            target_fps: TargetFps::default(),
+            max_updates_per_frame: 5,
+            is_paused: false,
+            n_steps: 0,
+            time_scale: 1.0,
+            max_elapsed: Duration::from_millis(500),
+            n_smoothing: 1,
+            recent_deltas: VecDeque::new(),
+        };
+        clock.set_target_fps(target_fps);
+        clock
     }
 
     /// TODO: is this accurate?
     fn target_elapsed(&self) -> Duration {
-        Duration::from_secs_f64(1.0 / self.updates_per_sec)
-    }
-
-    const fn max_elapsed() -> Duration {
-        Duration::from_millis(500)
+        self.target_fps.target_elapsed()
     }
 
     /// Returns way to tick one frame
     pub fn tick(&mut self) -> GameClockOneFrameTick {
+        self.n_frames += 1;
+        self.n_updates = 0;
+
         self.accum = {
+            // Pause and time scale only affect the game time; frames are still made in real time
+            let is_realtime = !self.is_paused && self.time_scale == 1.0;
+
+            let elapsed = if is_realtime {
+                self.wait_for_next_frame(self.accum)
+            } else {
+                let real_elapsed = self.wait_for_next_frame(Duration::new(0, 0));
+                if self.is_paused {
+                    self.accum
+                } else {
+                    self.accum + real_elapsed.mul_f64(self.time_scale)
+                }
+            };
+
             // Do not allow any update to take longer than our maximum.
-            let elapsed = self.wait_for_next_frame(self.accum);
-            if elapsed > Self::max_elapsed() {
-                Self::max_elapsed()
+            if elapsed > self.max_elapsed {
+                self.max_elapsed
             } else {
                 elapsed
             }
@@ -100,25 +286,180 @@ impl GameClock {
         self.time_step.clone()
     }
 
+    /// Blend factor between the last two simulation states in range `[0.0, 1.0)`
+    ///
+    /// [Fixed timestep only] It's the remaining accumulated time (that is not consumed by `update`
+    /// calls) normalized with the target elapsed time. Interpolate your rendering with it, e.g.,
+    /// `prev_pos * (1.0 - alpha) + pos * alpha`. Variable timestep always returns `1.0`.
+    pub fn alpha(&self) -> f32 {
+        if !self.target_fps.is_fixed() {
+            return 1.0;
+        }
+
+        let target_elapsed = self.target_elapsed();
+        let alpha = self.accum.as_secs_f64() / target_elapsed.as_secs_f64();
+        // `accum` can be bigger than one frame only after the update cap is hit
+        alpha.min(1.0) as f32
+    }
+
+    /// Timing of the current frame. Call it after running `update`s
+    pub fn timing(&self) -> FrameTiming {
+        FrameTiming {
+            total: self.total,
+            frame: self.n_frames,
+            n_updates: self.n_updates,
+            is_slow: self.is_slow,
+            lag: self.lag,
+        }
+    }
+
+    pub fn is_slow(&self) -> bool {
+        self.is_slow
+    }
+}
+
+/// Configuration
+impl GameClock {
+    pub fn target_fps(&self) -> TargetFps {
+        self.target_fps
+    }
+
+    /// Switches the timestep mode. It can be called while the game is running
+    ///
+    /// # Panics
+    ///
+    /// Panics if `TargetFps::Fixed(0)` is given.
+    pub fn set_target_fps(&mut self, target_fps: TargetFps) {
+        assert!(
+            target_fps != TargetFps::Fixed(0),
+            "fixed timestep requires non-zero updates per second"
+        );
+
+        if self.target_fps == target_fps {
+            return;
+        }
+
+        self.target_fps = target_fps;
+        // the remaining time was measured for the previous timestep
+        self.accum = Duration::new(0, 0);
+        self.lag = 0;
+        self.is_slow = false;
+        self.recent_deltas.clear();
+    }
+
+    pub fn max_elapsed(&self) -> Duration {
+        self.max_elapsed
+    }
+
+    /// Sets the upper limit of elapsed time in one frame (500 ms by default)
+    ///
+    /// Spikes (e.g. while the window is being dragged) are clamped to it.
+    pub fn set_max_elapsed(&mut self, max: Duration) {
+        self.max_elapsed = max;
+    }
+
+    pub fn n_smoothing(&self) -> usize {
+        self.n_smoothing
+    }
+
+    /// [Variable timestep only] Sets the number of frames to average `dt` over
+    ///
+    /// Moving-average smoothing hides OS scheduler hiccups. `1` (default) disables it.
+    pub fn set_n_smoothing(&mut self, n: usize) {
+        assert!(n > 0, "smoothing window must not be empty");
+        self.n_smoothing = n;
+        while self.recent_deltas.len() > n {
+            self.recent_deltas.pop_front();
+        }
+    }
+
+    pub fn max_updates_per_frame(&self) -> u32 {
+        self.max_updates_per_frame
+    }
+
+    /// [Fixed timestep only] Sets the maximum number of `update`s in one frame
+    ///
+    /// When the game can't catch up, the rest of the accumulated time is discarded so that a slow
+    /// machine doesn't spiral.
+    pub fn set_max_updates_per_frame(&mut self, n: u32) {
+        assert!(n > 0, "at least one update per frame is required");
+        self.max_updates_per_frame = n;
+    }
+}
+
+/// Pause, time scale and single-frame stepping
+///
+/// They affect `update`s only; the game keeps rendering in real time.
+impl GameClock {
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    pub fn pause(&mut self) {
+        self.is_paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.is_paused = false;
+        self.n_steps = 0;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        if self.is_paused {
+            self.resume();
+        } else {
+            self.pause();
+        }
+    }
+
+    /// Pauses the clock and requests exactly one `update` on the next tick
+    ///
+    /// The `update` is run with the target elapsed time (1/60 sec if variable timestep is
+    /// uncapped).
+    pub fn step(&mut self) {
+        self.is_paused = true;
+        self.n_steps += 1;
+    }
+
+    pub fn time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Slow motion (`< 1.0`) or fast forward (`> 1.0`)
+    ///
+    /// [Fixed timestep] `dt` stays the same; the number of `update`s changes.
+    pub fn set_time_scale(&mut self, scale: f64) {
+        assert!(scale >= 0.0, "time scale must be positive");
+        self.time_scale = scale;
+    }
+
+    /// `dt` of single-frame steps
+    fn step_elapsed(&self) -> Duration {
+        let dt = self.target_elapsed();
+        if dt == Duration::new(0, 0) {
+            TargetFps::default().target_elapsed()
+        } else {
+            dt
+        }
+    }
+}
+
+/// Internals
+impl GameClock {
     fn wait_for_next_frame(&mut self, accum: Duration) -> Duration {
         let mut elapsed = accum;
         loop {
             // Advance the accumulated elapsed time.
-            let now = Instant::now();
-            elapsed += now.duration_since(self.last_time);
+            let now = self.time_source.now();
+            elapsed += now - self.last_time;
             self.last_time = now;
 
-            if !self.is_fixed_timestep {
-                return elapsed;
-            }
-
             let target_elapsed = self.target_elapsed();
-            if elapsed > target_elapsed {
+            if elapsed >= target_elapsed {
                 break elapsed;
             } else {
-                // sleep (inaccurate but enough for making frames)
                 let remaining = target_elapsed - elapsed;
-                std::thread::sleep(remaining);
+                self.time_source.sleep(remaining);
             }
         }
     }
@@ -132,7 +473,6 @@ pub struct GameClockOneFrameTick<'a> {
 
 impl<'a> GameClockOneFrameTick<'a> {
     fn new(clock: &'a mut GameClock) -> Self {
-        clock.time_step = clock.target_elapsed();
         GameClockOneFrameTick {
             clock,
             n_updates: 0,
@@ -144,7 +484,9 @@ impl<'a> Iterator for GameClockOneFrameTick<'a> {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.clock.is_fixed_timestep {
+        if self.clock.is_paused {
+            self.next_step()
+        } else if self.clock.target_fps.is_fixed() {
             self.next_fixed()
         } else {
             self.next_variable()
@@ -154,14 +496,41 @@ impl<'a> Iterator for GameClockOneFrameTick<'a> {
 
 /// Internals
 impl<'a> GameClockOneFrameTick<'a> {
+    fn next_step(&mut self) -> Option<Duration> {
+        let dt = self.clock.step_elapsed();
+
+        if self.clock.n_steps == 0 {
+            self.clock.time_step = dt * self.n_updates;
+            return None;
+        }
+
+        self.clock.n_steps -= 1;
+        self.clock.total += dt;
+        self.n_updates += 1;
+        self.clock.n_updates = self.n_updates;
+
+        Some(dt)
+    }
+
     fn next_fixed(&mut self) -> Option<Duration> {
         let target_elapsed = self.clock.target_elapsed();
 
+        // Give up catching up if we can't
+        if self.n_updates >= self.clock.max_updates_per_frame && self.clock.accum >= target_elapsed
+        {
+            let n_skips = self.clock.accum.as_nanos() / target_elapsed.as_nanos();
+            log::trace!("lag: skipping {} updates", n_skips);
+            self.clock.accum = Duration::from_nanos(
+                (self.clock.accum.as_nanos() % target_elapsed.as_nanos()) as u64,
+            );
+        }
+
         // Perform as many full fixed length time steps as we can
         if self.clock.accum >= target_elapsed {
             self.clock.total += target_elapsed;
             self.clock.accum -= target_elapsed;
             self.n_updates += 1;
+            self.clock.n_updates = self.n_updates;
 
             if self.n_updates > 1 {
                 // FIXME:
@@ -173,7 +542,7 @@ impl<'a> GameClockOneFrameTick<'a> {
             }
 
             // update
-            return Some(self.clock.time_step.clone());
+            return Some(target_elapsed);
         }
 
         // Every update after the first accumulates lag
@@ -205,22 +574,149 @@ impl<'a> GameClockOneFrameTick<'a> {
             return None;
         }
 
-        // Perform a single variable length update.
-        // if forceElapsedTimeToZero {
-        //     // When ResetElapsedTime is called, Elapsed is forced to zero and Total is ignored entirely
-        //     time.elapsed = Duration::new(0, 0.0);
-        //     forceElapsedTimeToZero = false;
-        // } else {
-        self.clock.time_step = self.clock.accum;
-        self.clock.total += self.clock.accum;
-        // }
+        // Perform a single variable length update consuming all the accumulated time
+        let elapsed = self.clock.accum;
+        self.clock.accum = Duration::new(0, 0);
+
+        let dt = {
+            let deltas = &mut self.clock.recent_deltas;
+            deltas.push_back(elapsed);
+            while deltas.len() > self.clock.n_smoothing {
+                deltas.pop_front();
+            }
+            deltas.iter().sum::<Duration>() / deltas.len() as u32
+        };
+
+        self.clock.time_step = dt;
+        self.clock.total += dt;
 
-        self.clock.time_step = Duration::new(0, 0);
-        // AssertNotDisposed();
         self.n_updates = 1;
+        self.clock.n_updates = 1;
 
         Some(self.clock.time_step.clone())
 
         // On next call this method returns `None`
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn run_frames(clock: &mut GameClock, n_frames: usize) -> usize {
+        let mut n_updates = 0;
+        for _ in 0..n_frames {
+            n_updates += clock.tick().count();
+        }
+        n_updates
+    }
+
+    #[test]
+    fn test_fixed_one_update_per_frame() {
+        let mut clock = GameClock::with_time_source(TargetFps::Fixed(60), ManualTime::new());
+        assert_eq!(run_frames(&mut clock, 10_000), 10_000);
+        assert_eq!(clock.timing().frame, 10_000);
+    }
+
+    #[test]
+    fn test_fixed_catch_up() {
+        let time = ManualTime::new();
+        let mut clock = GameClock::with_time_source(TargetFps::Fixed(100), time.clone());
+
+        time.advance(Duration::from_millis(30));
+        assert_eq!(run_frames(&mut clock, 1), 3);
+        assert_eq!(clock.timing().lag, 2);
+
+        // capped to `max_updates_per_frame`
+        clock.set_max_updates_per_frame(2);
+        time.advance(Duration::from_millis(40));
+        assert_eq!(run_frames(&mut clock, 1), 2);
+        assert_eq!(clock.alpha(), 0.0);
+    }
+
+    #[test]
+    fn test_pause_and_step() {
+        let time = ManualTime::stepped(Duration::from_millis(10));
+        let mut clock = GameClock::with_time_source(TargetFps::Fixed(100), time);
+
+        clock.pause();
+        assert_eq!(run_frames(&mut clock, 100), 0);
+        assert_eq!(clock.timing().total, Duration::new(0, 0));
+
+        clock.step();
+        clock.step();
+        assert_eq!(run_frames(&mut clock, 100), 2);
+        assert!(clock.is_paused());
+
+        clock.resume();
+        assert_eq!(run_frames(&mut clock, 100), 100);
+    }
+
+    #[test]
+    fn test_time_scale() {
+        let time = ManualTime::stepped(Duration::from_millis(10));
+        let mut clock = GameClock::with_time_source(TargetFps::Fixed(100), time);
+
+        clock.set_time_scale(0.5);
+        assert_eq!(run_frames(&mut clock, 100), 50);
+
+        clock.set_time_scale(2.0);
+        assert_eq!(run_frames(&mut clock, 100), 200);
+    }
+
+    #[test]
+    fn test_variable_delta() {
+        let time = ManualTime::new();
+        let mut clock = GameClock::with_time_source(TargetFps::Variable(0), time.clone());
+
+        for ms in &[5, 16, 33] {
+            time.advance(Duration::from_millis(*ms));
+            let dts = clock.tick().collect::<Vec<_>>();
+            assert_eq!(dts, vec![Duration::from_millis(*ms)]);
+            assert_eq!(clock.timestep_draw(), Duration::from_millis(*ms));
+        }
+        assert_eq!(clock.timing().total, Duration::from_millis(5 + 16 + 33));
+    }
+
+    #[test]
+    fn test_variable_cap() {
+        let mut clock = GameClock::with_time_source(TargetFps::Variable(100), ManualTime::new());
+
+        let dts = clock.tick().collect::<Vec<_>>();
+        assert_eq!(dts, vec![Duration::from_millis(10)]);
+    }
+
+    #[test]
+    fn test_variable_clamp_and_smoothing() {
+        let time = ManualTime::new();
+        let mut clock = GameClock::with_time_source(TargetFps::Variable(0), time.clone());
+        clock.set_max_elapsed(Duration::from_millis(100));
+        clock.set_n_smoothing(2);
+
+        time.advance(Duration::from_millis(10));
+        assert_eq!(clock.tick().next(), Some(Duration::from_millis(10)));
+
+        // spike clamped to 100 ms and averaged with the last frame
+        time.advance(Duration::from_secs(3));
+        assert_eq!(clock.tick().next(), Some(Duration::from_millis(55)));
+
+        time.advance(Duration::from_millis(10));
+        assert_eq!(clock.tick().next(), Some(Duration::from_millis(55)));
+
+        time.advance(Duration::from_millis(10));
+        assert_eq!(clock.tick().next(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn test_switch_target_fps() {
+        let mut clock = GameClock::with_time_source(TargetFps::Fixed(60), ManualTime::new());
+        assert_eq!(run_frames(&mut clock, 60), 60);
+
+        clock.set_target_fps(TargetFps::Fixed(120));
+        assert_eq!(run_frames(&mut clock, 120), 120);
+        assert_eq!(
+            clock.timing().total,
+            Duration::from_secs_f64(1.0 / 60.0) * 60 + Duration::from_secs_f64(1.0 / 120.0) * 120
+        );
+    }
+}
@@ -0,0 +1,348 @@
+//! Runs [`AnfLifecycle`] without window or graphics device
+//!
+//! Useful for integration tests and simulation servers.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use anf::engine::{core::headless::HeadlessRunner, prelude::*};
+//!
+//! #[derive(Default)]
+//! struct Game {
+//!     n_updates: usize,
+//! }
+//!
+//! impl AnfLifecycle for Game {
+//!     fn update(&mut self, _dt: std::time::Duration) -> AnfResult<()> {
+//!         self.n_updates += 1;
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let mut game = Game::default();
+//! let mut runner = HeadlessRunner::new(TargetFps::Fixed(60));
+//! runner.run_frames(&mut game, 10_000).unwrap();
+//! assert_eq!(game.n_updates, 10_000);
+//! ```
+
+use {
+    sdl2::event::Event,
+    std::{collections::VecDeque, time::Duration},
+};
+
+use crate::engine::core::{
+    clock::{FrameTiming, GameClock, ManualTime, TargetFps},
+    lifecycle::{self, AnfLifecycle, AnfResult},
+};
+
+/// Drives the game loop of [`AnfLifecycle`] with synthetic events
+///
+/// Each frame runs the same stages as `AnfFramework` does: `event`, `on_tick`, `update`,
+/// `on_frame_timing`, `render` and `on_end_frame`. `init` is called on the first frame and
+/// `on_exit` is called when the game quits or [`HeadlessRunner::exit`] is called.
+#[derive(Debug)]
+pub struct HeadlessRunner {
+    clock: GameClock,
+    /// Events delivered on the next frame
+    events: VecDeque<Event>,
+    is_initialized: bool,
+    is_exited: bool,
+}
+
+impl HeadlessRunner {
+    /// Creates a runner with simulated time, where every frame takes exactly one target frame
+    ///
+    /// It runs as fast as possible (not in real time).
+    pub fn new(target_fps: TargetFps) -> Self {
+        let step = match target_fps.target_elapsed() {
+            dt if dt == Duration::new(0, 0) => TargetFps::default().target_elapsed(),
+            dt => dt,
+        };
+
+        Self::with_clock(GameClock::with_time_source(
+            target_fps,
+            ManualTime::stepped(step),
+        ))
+    }
+
+    /// Creates a runner with any clock, e.g., a real time clock for simulation servers
+    pub fn with_clock(clock: GameClock) -> Self {
+        Self {
+            clock,
+            events: VecDeque::new(),
+            is_initialized: false,
+            is_exited: false,
+        }
+    }
+
+    pub fn clock(&self) -> &GameClock {
+        &self.clock
+    }
+
+    pub fn clock_mut(&mut self) -> &mut GameClock {
+        &mut self.clock
+    }
+
+    pub fn timing(&self) -> FrameTiming {
+        self.clock.timing()
+    }
+
+    /// Injects an event. It's delivered on the next frame
+    pub fn push_event(&mut self, ev: Event) {
+        self.events.push_back(ev);
+    }
+
+    /// Runs one frame. Returns `true` if the game quit
+    ///
+    /// Does nothing and returns `true` after the game quit. If the frame fails, the game quits
+    /// (calling `on_exit`) and the error is returned.
+    pub fn run_frame(&mut self, state: &mut impl AnfLifecycle) -> AnfResult<bool> {
+        if self.is_exited {
+            return Ok(true);
+        }
+
+        match self.run_stages(state) {
+            Ok(false) => Ok(false),
+            res => {
+                // `on_exit` is called even if the frame fails
+                let exit = self.exit(state);
+                res.and_then(|quit| exit.map(|()| quit))
+            }
+        }
+    }
+
+    fn run_stages(&mut self, state: &mut impl AnfLifecycle) -> AnfResult<bool> {
+        if !self.is_initialized {
+            self.is_initialized = true;
+            state.init()?;
+        }
+
+        let events = self.events.drain(..).collect::<Vec<_>>();
+        lifecycle::run_one_frame(events.into_iter(), &mut self.clock, state, None)
+    }
+
+    /// Ends the game, calling `on_exit` if it's not called yet
+    pub fn exit(&mut self, state: &mut impl AnfLifecycle) -> AnfResult<()> {
+        if self.is_exited {
+            return Ok(());
+        }
+        self.is_exited = true;
+        state.on_exit()
+    }
+
+    /// Runs `n_frames` frames. Returns `true` if the game quit before that
+    pub fn run_frames(
+        &mut self,
+        state: &mut impl AnfLifecycle,
+        n_frames: usize,
+    ) -> AnfResult<bool> {
+        for _ in 0..n_frames {
+            if self.run_frame(state)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Runs frames until the predicate returns `true` (checked after each frame) or the game quits
+    ///
+    /// Returns `true` if the game quit.
+    pub fn run_until<T: AnfLifecycle>(
+        &mut self,
+        state: &mut T,
+        mut pred: impl FnMut(&T, &FrameTiming) -> bool,
+    ) -> AnfResult<bool> {
+        loop {
+            if self.run_frame(state)? {
+                return Ok(true);
+            }
+            if pred(state, &self.clock.timing()) {
+                return Ok(false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use sdl2::event::WindowEvent;
+
+    #[derive(Default)]
+    struct Game {
+        n_updates: usize,
+        n_frames: usize,
+        /// Frames where events are delivered
+        event_frames: Vec<usize>,
+        /// Called hooks
+        hooks: Vec<String>,
+        n_exits: usize,
+        cancels_quit: bool,
+        fails_update: bool,
+    }
+
+    impl AnfLifecycle for Game {
+        fn event(&mut self, _ev: &Event) -> AnfResult<()> {
+            self.event_frames.push(self.n_frames);
+            Ok(())
+        }
+
+        fn update(&mut self, _dt: Duration) -> AnfResult<()> {
+            if self.fails_update {
+                anyhow::bail!("update failed");
+            }
+            self.n_updates += 1;
+            Ok(())
+        }
+
+        fn on_end_frame(&mut self) -> AnfResult<()> {
+            self.n_frames += 1;
+            Ok(())
+        }
+
+        fn on_exit(&mut self) -> AnfResult<()> {
+            self.n_exits += 1;
+            Ok(())
+        }
+
+        fn on_quit_request(&mut self) -> AnfResult<bool> {
+            self.hooks.push("quit_request".to_string());
+            Ok(!self.cancels_quit)
+        }
+
+        fn on_resize(&mut self, w: u32, h: u32) -> AnfResult<()> {
+            self.hooks.push(format!("resize {}x{}", w, h));
+            Ok(())
+        }
+
+        fn on_focus_lost(&mut self) -> AnfResult<()> {
+            self.hooks.push("focus_lost".to_string());
+            Ok(())
+        }
+
+        fn on_minimize(&mut self) -> AnfResult<()> {
+            self.hooks.push("minimize".to_string());
+            Ok(())
+        }
+    }
+
+    fn runner() -> HeadlessRunner {
+        HeadlessRunner::new(TargetFps::Fixed(60))
+    }
+
+    fn window_event(win_event: WindowEvent) -> Event {
+        Event::Window {
+            timestamp: 0,
+            window_id: 0,
+            win_event,
+        }
+    }
+
+    #[test]
+    fn test_run_frames() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        assert!(!runner.run_frames(&mut game, 100).unwrap());
+        assert_eq!(game.n_updates, 100);
+        assert_eq!(game.n_frames, 100);
+    }
+
+    #[test]
+    fn test_push_event() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        runner.run_frames(&mut game, 2).unwrap();
+
+        runner.push_event(window_event(WindowEvent::Exposed));
+        assert!(game.event_frames.is_empty());
+
+        runner.run_frames(&mut game, 3).unwrap();
+        assert_eq!(game.event_frames, vec![2]);
+    }
+
+    #[test]
+    fn test_run_until() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        let quit = runner
+            .run_until(&mut game, |game, _timing| game.n_updates >= 5)
+            .unwrap();
+        assert!(!quit);
+        assert_eq!(game.n_updates, 5);
+    }
+
+    #[test]
+    fn test_quit_event() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        runner.run_frames(&mut game, 1).unwrap();
+
+        runner.push_event(Event::Quit { timestamp: 0 });
+        assert!(runner.run_frames(&mut game, 10).unwrap());
+        // the frame is not run after quitting
+        assert_eq!(game.n_updates, 1);
+        assert!(runner.run_frame(&mut game).unwrap());
+    }
+
+    #[test]
+    fn test_cancel_quit() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        game.cancels_quit = true;
+
+        // closing the last window (SDL sends `Quit` right after `Close`)
+        runner.push_event(window_event(WindowEvent::Close));
+        runner.push_event(Event::Quit { timestamp: 0 });
+        assert!(!runner.run_frame(&mut game).unwrap());
+        assert_eq!(game.hooks, vec!["quit_request"]);
+        assert_eq!(game.n_updates, 1);
+        assert_eq!(game.n_exits, 0);
+
+        // `Quit` alone is still asked
+        runner.push_event(Event::Quit { timestamp: 0 });
+        assert!(!runner.run_frame(&mut game).unwrap());
+        assert_eq!(game.hooks, vec!["quit_request", "quit_request"]);
+
+        game.cancels_quit = false;
+        runner.push_event(window_event(WindowEvent::Close));
+        assert!(runner.run_frame(&mut game).unwrap());
+        assert_eq!(game.n_exits, 1);
+    }
+
+    #[test]
+    fn test_exit_once() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        runner.push_event(Event::Quit { timestamp: 0 });
+        runner.run_frames(&mut game, 2).unwrap();
+        runner.exit(&mut game).unwrap();
+        assert_eq!(game.n_exits, 1);
+    }
+
+    #[test]
+    fn test_exit_on_error() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        game.fails_update = true;
+
+        assert!(runner.run_frame(&mut game).is_err());
+        assert_eq!(game.n_exits, 1);
+
+        // the game is already over
+        assert!(runner.run_frame(&mut game).unwrap());
+        assert_eq!(game.n_exits, 1);
+    }
+
+    #[test]
+    fn test_window_hooks() {
+        let (mut game, mut runner) = (Game::default(), runner());
+        game.cancels_quit = true;
+
+        runner.push_event(window_event(WindowEvent::SizeChanged(640, 480)));
+        runner.push_event(window_event(WindowEvent::FocusLost));
+        runner.push_event(window_event(WindowEvent::Minimized));
+        runner.push_event(window_event(WindowEvent::Close));
+        runner.run_frame(&mut game).unwrap();
+
+        assert_eq!(
+            game.hooks,
+            vec!["resize 640x480", "focus_lost", "minimize", "quit_request"]
+        );
+        // `event` is called for each of them
+        assert_eq!(game.event_frames.len(), 4);
+    }
+}
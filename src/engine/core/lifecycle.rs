@@ -1,14 +1,23 @@
 //! The primitive lifecycle
 
 use ::{
-    sdl2::{event::Event, EventPump},
-    std::time::Duration,
+    fna3h::{win::PresentationParameters, Device},
+    sdl2::{
+        event::{Event, WindowEvent},
+        EventPump,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        path::PathBuf,
+        rc::{Rc, Weak},
+        time::Duration,
+    },
 };
 
 use crate::engine::{
     core::{
         clock::*,
-        window::{WindowConfig, WindowHandle},
+        window::{self, DeviceContext, WindowConfig, WindowHandle},
     },
     draw::*,
 };
@@ -21,25 +30,107 @@ pub type AnfResult<T> = ::anyhow::Result<T>;
 /// Users are encouraged to build their own framework on top of it, maybe specifying stages such as
 /// `debug_render`.
 pub trait AnfLifecycle {
+    /// Called once before the first frame
+    fn init(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
     // TODO: lifecycle with `EventPump` with window?
+    /// Called for every event of every window except `Event::Quit` (see
+    /// [`AnfLifecycle::on_quit_request`])
+    ///
+    /// Route events of sub windows with [`WindowHandle::is_event_target`].
     #[allow(unused_variables)]
     fn event(&mut self, ev: &Event) -> AnfResult<()> {
         Ok(())
     }
 
+    /// Called every frame after pumping events and before ticking the clock
+    ///
+    /// Switch [`TargetFps`] or other clock settings here.
+    #[allow(unused_variables)]
+    fn on_tick(&mut self, clock: &mut GameClock) -> AnfResult<()> {
+        Ok(())
+    }
+
     #[allow(unused_variables)]
     fn update(&mut self, dt: Duration) -> AnfResult<()> {
         Ok(())
     }
 
+    /// Called every frame after `update`s with the timing of the frame
+    #[allow(unused_variables)]
+    fn on_frame_timing(&mut self, timing: &FrameTiming) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// `alpha` is the blend factor between the last two `update`s (see [`GameClock::alpha`])
     #[allow(unused_variables)]
-    fn render(&mut self, dt: Duration) -> AnfResult<()> {
+    fn render(&mut self, dt: Duration, alpha: f32) -> AnfResult<()> {
         Ok(())
     }
 
     fn on_end_frame(&mut self) -> AnfResult<()> {
         Ok(())
     }
+
+    /// Checked at the end of every frame. Return `true` to quit the game without asking
+    /// [`AnfLifecycle::on_quit_request`] (e.g. after the player confirmed quitting)
+    fn should_quit(&self) -> bool {
+        false
+    }
+
+    /// Called once when the game loop ends, even if it ends with an error
+    fn on_exit(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    // window/application events (called after `event`, only for the main window)
+
+    /// Called on `Event::Quit` or when the main window is closed. Return `false` to cancel quitting
+    fn on_quit_request(&mut self) -> AnfResult<bool> {
+        Ok(true)
+    }
+
+    /// Called when the window size is changed (in window points)
+    ///
+    /// The backbuffer is already resized to the drawable size in pixels.
+    #[allow(unused_variables)]
+    fn on_resize(&mut self, w: u32, h: u32) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Called when pixels per point changes (e.g. the window moved to another display)
+    #[allow(unused_variables)]
+    fn on_dpi_changed(&mut self, dpi_scale: f32) -> AnfResult<()> {
+        Ok(())
+    }
+
+    fn on_focus_gained(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    fn on_focus_lost(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    fn on_minimize(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    fn on_restore(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Called when the application is going to the background (mobile platforms)
+    fn on_suspend(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Called when the application came back to the foreground (mobile platforms)
+    fn on_resume(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
 }
 
 /// The entry point of ANF application; runs a game that implemenents [`AnfLifecycle`]
@@ -48,23 +139,21 @@ pub struct AnfFramework {
     window: WindowHandle,
     events: sdl2::EventPump,
     dcx: DrawContext,
+    backbuffers: Rc<Backbuffers>,
 }
 
 impl AnfFramework {
     pub fn from_cfg(cfg: WindowConfig) -> Self {
-        let (mut window, dcx) = {
-            let (window, device, params) = crate::engine::core::init(&cfg);
-            let dcx = DrawContext::new(device, crate::engine::embedded::SPRITE_EFFECT, params);
-            (window, dcx)
-        };
-
+        let (mut window, dcx) = crate::engine::core::init(&cfg);
         let events = window.event_pump().unwrap();
+        let backbuffers = Rc::clone(&window.backbuffers);
 
         Self {
             cfg,
             window,
             events,
             dcx,
+            backbuffers,
         }
     }
 
@@ -77,47 +166,343 @@ impl AnfFramework {
             window,
             dcx,
             mut events,
+            backbuffers,
         } = self;
 
         let mut state = gen_user_data(window, &cfg, dcx);
+        let mut clock = GameClock::with_target_fps(cfg.target_fps);
 
-        self::run_game_loop(&mut events, &mut state)
+        self::run_game_loop(&mut events, &mut clock, &mut state, Some(&backbuffers))
     }
 }
 
-fn run_game_loop(events: &mut EventPump, state: &mut impl AnfLifecycle) -> AnfResult<()> {
-    // HACK: skip the first 1 frame so that the window opens
-    if self::pump_events(state, events)? {
-        unreachable!();
+/// Creates FNA3D device and [`DrawContext`] for the window, registering the backbuffer to the
+/// framework
+pub(crate) fn create_draw_context(window: &mut WindowHandle, cfg: &WindowConfig) -> DrawContext {
+    let (params, device) =
+        crate::engine::core::create_fna3d_device(cfg, window.raw_window(), window.drawable_size());
+
+    let dcx = DrawContext::new(
+        device.clone(),
+        crate::engine::embedded::SPRITE_EFFECT,
+        params,
+    );
+
+    let backbuffer = Rc::new(Backbuffer {
+        device,
+        params: Cell::new(params),
+        size: dcx.shared_backbuffer_size(),
+        screenshot_requests: dcx.shared_screenshot_requests(),
+        context: DeviceContext::current(window.raw_window()),
+        window_id: window.id(),
+        dpi_scale: Cell::new(window.dpi_scale()),
+        auto_present: cfg.auto_present,
+    });
+
+    window.backbuffers.register(&backbuffer);
+    window.backbuffer = Some(backbuffer);
+
+    dcx
+}
+
+/// Backbuffers of the windows, managed by the framework
+///
+/// The first window is the main window. Backbuffers of dropped windows are removed.
+#[derive(Default)]
+pub(crate) struct Backbuffers {
+    list: RefCell<Vec<Weak<Backbuffer>>>,
+    main_window_id: Cell<Option<u32>>,
+}
+
+impl Backbuffers {
+    fn register(&self, backbuffer: &Rc<Backbuffer>) {
+        if self.main_window_id.get().is_none() {
+            self.main_window_id.set(Some(backbuffer.window_id));
+        }
+        self.list.borrow_mut().push(Rc::downgrade(backbuffer));
+    }
+
+    /// Alive backbuffers
+    fn iter(&self) -> Vec<Rc<Backbuffer>> {
+        let mut list = self.list.borrow_mut();
+        list.retain(|b| b.upgrade().is_some());
+        list.iter().filter_map(|b| b.upgrade()).collect()
+    }
+
+    fn find(&self, window_id: u32) -> Option<Rc<Backbuffer>> {
+        self.iter().into_iter().find(|b| b.window_id == window_id)
     }
 
-    let mut clock = GameClock::new();
+    fn is_main_window(&self, window_id: u32) -> bool {
+        self.main_window_id.get().map_or(true, |id| id == window_id)
+    }
+
+    fn present(&self) {
+        for backbuffer in self.iter() {
+            backbuffer.present();
+        }
+    }
+}
+
+/// Backbuffer of a window
+///
+/// It's resized on resize events and presented after `render`. Shared with [`WindowHandle`] to
+/// reset the backbuffer on fullscreen mode changes.
+pub(crate) struct Backbuffer {
+    device: Device,
+    params: Cell<PresentationParameters>,
+    /// Shared with [`DrawContext`], which updates the viewport and projection matrix with it
+    size: Rc<Cell<[u32; 2]>>,
+    /// Requested by [`DrawContext::request_screenshot`]
+    screenshot_requests: Rc<RefCell<Vec<PathBuf>>>,
+    context: DeviceContext,
+    window_id: u32,
+    /// Pixels per point of the window
+    dpi_scale: Cell<f32>,
+    auto_present: bool,
+}
+
+impl Backbuffer {
+    fn raw_window(&self) -> *mut sdl2::sys::SDL_Window {
+        self.params.get().deviceWindowHandle as *mut _
+    }
+
+    fn present(&self) {
+        self.save_screenshots();
+
+        if self.auto_present {
+            self.context.make_current();
+            self.device
+                .swap_buffers(None, None, self.raw_window() as *mut _);
+        }
+    }
+
+    fn save_screenshots(&self) {
+        let paths = std::mem::take(&mut *self.screenshot_requests.borrow_mut());
+        if paths.is_empty() {
+            return;
+        }
+
+        self.context.make_current();
+        let image = crate::engine::draw::read_backbuffer(&self.device, self.size.get());
+        for path in paths {
+            match image.save_png(&path) {
+                Ok(()) => log::info!("screenshot saved to `{}`", path.display()),
+                Err(err) => log::warn!("failed to save `{}`: {}", path.display(), err),
+            }
+        }
+    }
+
+    /// Resizes the backbuffer to the drawable size of the window. Returns the new DPI scale if
+    /// it's changed
+    pub(crate) fn sync(&self) -> Option<f32> {
+        let [w, h] = window::raw_drawable_size(self.raw_window());
+        self.resize(w, h);
+
+        let scale = window::dpi_scale(self.raw_window());
+        if (scale - self.dpi_scale.get()).abs() > std::f32::EPSILON {
+            self.dpi_scale.set(scale);
+            Some(scale)
+        } else {
+            None
+        }
+    }
+
+    fn resize(&self, w: u32, h: u32) {
+        if self.size.get() == [w, h] {
+            return;
+        }
+
+        let mut params = self.params.get();
+        params.backBufferWidth = w as i32;
+        params.backBufferHeight = h as i32;
+        self.device.reset_backbuffer(&params);
+
+        self.params.set(params);
+        self.size.set([w, h]);
+    }
+}
+
+fn run_game_loop(
+    events: &mut EventPump,
+    clock: &mut GameClock,
+    state: &mut impl AnfLifecycle,
+    backbuffers: Option<&Backbuffers>,
+) -> AnfResult<()> {
+    let res = self::run_frames(events, clock, state, backbuffers);
+    // `on_exit` is called even if the game loop fails
+    let exit = state.on_exit();
+    res.and(exit)
+}
+
+fn run_frames(
+    events: &mut EventPump,
+    clock: &mut GameClock,
+    state: &mut impl AnfLifecycle,
+    backbuffers: Option<&Backbuffers>,
+) -> AnfResult<()> {
+    state.init()?;
+
+    // HACK: skip the first 1 frame so that the window opens
+    if self::pump_events(state, events.poll_iter(), backbuffers)? {
+        return Ok(()); // quit is requested before the first frame
+    }
 
     loop {
-        if self::pump_events(state, events)? {
+        if self::run_one_frame(events.poll_iter(), clock, state, backbuffers)? {
             return Ok(()); // close the game window
         }
+    }
+}
+
+/// Runs one frame of the game loop. Returns `true` if the game should quit
+///
+/// Shared with the headless runner (without backbuffer).
+pub(crate) fn run_one_frame(
+    events: impl Iterator<Item = Event>,
+    clock: &mut GameClock,
+    state: &mut impl AnfLifecycle,
+    backbuffers: Option<&Backbuffers>,
+) -> AnfResult<bool> {
+    #[cfg(feature = "profiler")]
+    crate::engine::profiler::with(|p| p.begin_frame());
 
-        for dt in clock.tick() {
-            state.update(dt)?;
+    let res = self::run_stages(events, clock, state, backbuffers);
+
+    #[cfg(feature = "profiler")]
+    crate::engine::profiler::with(|p| p.end_frame());
+
+    res
+}
+
+fn run_stages(
+    events: impl Iterator<Item = Event>,
+    clock: &mut GameClock,
+    state: &mut impl AnfLifecycle,
+    backbuffers: Option<&Backbuffers>,
+) -> AnfResult<bool> {
+    {
+        crate::profile_scope!("event");
+        if self::pump_events(state, events, backbuffers)? {
+            return Ok(true);
         }
+    }
+
+    state.on_tick(clock)?;
+
+    for dt in clock.tick() {
+        crate::profile_scope!("update");
+        state.update(dt)?;
+    }
 
+    state.on_frame_timing(&clock.timing())?;
+
+    {
+        crate::profile_scope!("render");
         let time_step = clock.timestep_draw();
-        state.render(time_step)?;
+        state.render(time_step, clock.alpha())?;
+    }
 
-        state.on_end_frame()?;
+    if let Some(backbuffers) = backbuffers {
+        crate::profile_scope!("present");
+        backbuffers.present();
     }
+
+    state.on_end_frame()?;
+
+    Ok(state.should_quit())
 }
 
 /// Returns `true` if the window should be closed
-fn pump_events(state: &mut impl AnfLifecycle, events: &mut EventPump) -> AnfResult<bool> {
-    for ev in events.poll_iter() {
+fn pump_events(
+    state: &mut impl AnfLifecycle,
+    events: impl Iterator<Item = Event>,
+    backbuffers: Option<&Backbuffers>,
+) -> AnfResult<bool> {
+    // closing the last window sends `Event::Quit` right after `WindowEvent::Close`. We ask only
+    // once per close
+    let mut is_after_close = false;
+
+    for ev in events {
         match ev {
-            Event::Quit { .. } => return Ok(true),
+            Event::Quit { .. } => {
+                if std::mem::replace(&mut is_after_close, false) {
+                    continue;
+                }
+                if state.on_quit_request()? {
+                    return Ok(true);
+                }
+            }
             ev => {
+                is_after_close = false;
+
+                // resize the backbuffer before the game sees the event. Moving the window to
+                // another display can change the DPI scale
+                let mut new_dpi_scale = None;
+                let mut is_main_window = true;
+                if let (
+                    Some(backbuffers),
+                    Event::Window {
+                        window_id,
+                        win_event,
+                        ..
+                    },
+                ) = (backbuffers, &ev)
+                {
+                    is_main_window = backbuffers.is_main_window(*window_id);
+                    if matches!(
+                        win_event,
+                        WindowEvent::SizeChanged(..) | WindowEvent::Moved(..)
+                    ) {
+                        if let Some(backbuffer) = backbuffers.find(*window_id) {
+                            new_dpi_scale = backbuffer.sync();
+                        }
+                    }
+                }
+
                 state.event(&ev)?;
+
+                // window hooks are only for the main window (sub windows are handled in `event`)
+                if !is_main_window {
+                    continue;
+                }
+
+                // SDL doesn't send `Event::Quit` while sub windows are open
+                if let Event::Window {
+                    win_event: WindowEvent::Close,
+                    ..
+                } = ev
+                {
+                    if state.on_quit_request()? {
+                        return Ok(true);
+                    }
+                    is_after_close = true;
+                }
+
+                self::dispatch_event(state, &ev)?;
+
+                if let Some(scale) = new_dpi_scale {
+                    state.on_dpi_changed(scale)?;
+                }
             }
         }
     }
     Ok(false)
 }
+
+/// Calls window/application event hooks of [`AnfLifecycle`]
+fn dispatch_event(state: &mut impl AnfLifecycle, ev: &Event) -> AnfResult<()> {
+    match ev {
+        Event::Window { win_event, .. } => match win_event {
+            WindowEvent::SizeChanged(w, h) => state.on_resize(*w as u32, *h as u32),
+            WindowEvent::FocusGained => state.on_focus_gained(),
+            WindowEvent::FocusLost => state.on_focus_lost(),
+            WindowEvent::Minimized => state.on_minimize(),
+            WindowEvent::Restored => state.on_restore(),
+            _ => Ok(()),
+        },
+        Event::AppWillEnterBackground { .. } => state.on_suspend(),
+        Event::AppDidEnterForeground { .. } => state.on_resume(),
+        _ => Ok(()),
+    }
+}
@@ -1,7 +1,10 @@
 //! Core of the ANF engine
 
 pub mod clock;
+pub mod headless;
 pub mod lifecycle;
+#[cfg(feature = "settings")]
+pub mod settings;
 pub mod window;
 
 use {
@@ -13,36 +16,50 @@ use {
     sdl2::sys::SDL_Window,
 };
 
+use crate::engine::draw::DrawContext;
+
 use self::window::{WindowConfig, WindowHandle};
 
-/// Returns `(window, device, params): (WindowHandle, Device, PresentationParameters)`
-///
-/// The `device` is set initial states so that it can soon be used:
-///
-/// * pre-multiplied alpha `BlendState`
-/// * viewport with size of the screen
+/// Returns `(window, dcx): (WindowHandle, DrawContext)`
 ///
-/// TODO: note about the presentation parameters
-fn init(cfg: &WindowConfig) -> (WindowHandle, Device, PresentationParameters) {
+/// Sub windows are opened with [`WindowHandle::open_window`]. See [`create_fna3d_device`] for
+/// the device states.
+fn init(cfg: &WindowConfig) -> (WindowHandle, DrawContext) {
     // setup FNA3D
     log::info!("FNA version {}", fna3h::fna3d::linked_version());
     fna3h::win::hook_log_functions_default();
 
-    let win = WindowHandle::from_cfg(&cfg);
-    let (params, device) = self::create_fna3d_device(cfg, win.raw_window());
+    let mut win = WindowHandle::from_cfg(&cfg);
+    let dcx = lifecycle::create_draw_context(&mut win, cfg);
 
-    (win, device, params)
+    (win, dcx)
 }
 
+/// Returns `(params, device): (PresentationParameters, Device)`
+///
+/// The `device` is set initial states so that it can soon be used:
+///
+/// * pre-multiplied alpha `BlendState`
+/// * viewport with size of the screen
+///
+/// The presentation parameters are made from the window and then overwritten with the
+/// [`WindowConfig`]: backbuffer format, multisampling, depth-stencil format and presentation
+/// interval. The backbuffer is sized in pixels (`drawable_size`), which is larger than the window
+/// size in points on high-DPI displays.
 fn create_fna3d_device(
     cfg: &WindowConfig,
     raw_window: *mut SDL_Window,
+    drawable_size: (u32, u32),
 ) -> (PresentationParameters, Device) {
     let params = {
         let mut params =
             fna3h::fna3d::utils::default_params_from_window_handle(raw_window as *mut _);
-        params.backBufferWidth = cfg.w as i32;
-        params.backBufferHeight = cfg.h as i32;
+        params.backBufferWidth = drawable_size.0 as i32;
+        params.backBufferHeight = drawable_size.1 as i32;
+        params.backBufferFormat = cfg.backbuffer_format;
+        params.multiSampleCount = cfg.multisample_count as i32;
+        params.depthStencilFormat = cfg.depth_stencil_format;
+        params.presentationInterval = cfg.present_interval;
         params
     };
 
@@ -0,0 +1,349 @@
+/*! Window settings file and command line overrides (enabled with `settings` feature)
+
+[`WindowConfig::load_settings_with_args`] overrides the config with a TOML file and command line
+arguments ([`WindowConfig::load_settings`] for the file only), and the window state (size, position
+and fullscreen mode) is saved to the file when the [`WindowHandle`] is dropped. The size and
+position are saved only while the window is windowed:
+
+```no_run
+use anf::engine::prelude::*;
+
+let mut cfg = WindowConfig::default();
+cfg.load_settings_with_args("settings.toml", std::env::args().skip(1))
+    .unwrap();
+```
+
+Settings file:
+
+```toml
+w = 1280
+h = 720
+x = 100
+y = 100
+vsync = true
+# "windowed", "borderless" or "exclusive"
+fullscreen = "exclusive"
+# display mode of exclusive fullscreen (optional)
+mode_w = 1920
+mode_h = 1080
+refresh_rate = 60
+```
+
+Command line flags: `--width <w>`, `--height <h>`, `--fullscreen`, `--windowed`, `--vsync` and
+`--no-vsync`.
+!*/
+
+use {
+    fna3h::win::PresentInterval,
+    serde::{Deserialize, Serialize},
+    std::{fs, io, path::Path},
+};
+
+use crate::engine::core::{
+    lifecycle::AnfResult,
+    window::{DisplayMode, FullscreenMode, WindowConfig, WindowHandle},
+};
+
+/// Overrides of [`WindowConfig`]. `None` fields are not applied
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(from = "SettingsFile", into = "SettingsFile")]
+pub struct WindowSettings {
+    /// Window width in points
+    pub w: Option<u32>,
+    /// Window height in points
+    pub h: Option<u32>,
+    pub x: Option<i32>,
+    pub y: Option<i32>,
+    pub vsync: Option<bool>,
+    pub fullscreen: Option<FullscreenMode>,
+}
+
+/// Flat representation of [`WindowSettings`] (TOML can't serialize enum variants with data)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct SettingsFile {
+    w: Option<u32>,
+    h: Option<u32>,
+    x: Option<i32>,
+    y: Option<i32>,
+    vsync: Option<bool>,
+    fullscreen: Option<FullscreenKind>,
+    /// [Exclusive] Display mode
+    mode_w: Option<u32>,
+    mode_h: Option<u32>,
+    refresh_rate: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum FullscreenKind {
+    Windowed,
+    Borderless,
+    Exclusive,
+}
+
+impl From<SettingsFile> for WindowSettings {
+    fn from(file: SettingsFile) -> Self {
+        let display_mode = match (file.mode_w, file.mode_h) {
+            (Some(w), Some(h)) => Some(DisplayMode {
+                w,
+                h,
+                refresh_rate: file.refresh_rate.unwrap_or(0),
+            }),
+            _ => None,
+        };
+
+        Self {
+            w: file.w,
+            h: file.h,
+            x: file.x,
+            y: file.y,
+            vsync: file.vsync,
+            fullscreen: file.fullscreen.map(|kind| match kind {
+                FullscreenKind::Windowed => FullscreenMode::Windowed,
+                FullscreenKind::Borderless => FullscreenMode::Borderless,
+                FullscreenKind::Exclusive => FullscreenMode::Exclusive(display_mode),
+            }),
+        }
+    }
+}
+
+impl From<WindowSettings> for SettingsFile {
+    fn from(settings: WindowSettings) -> Self {
+        let (fullscreen, display_mode) = match settings.fullscreen {
+            None => (None, None),
+            Some(FullscreenMode::Windowed) => (Some(FullscreenKind::Windowed), None),
+            Some(FullscreenMode::Borderless) => (Some(FullscreenKind::Borderless), None),
+            Some(FullscreenMode::Exclusive(mode)) => (Some(FullscreenKind::Exclusive), mode),
+        };
+
+        Self {
+            w: settings.w,
+            h: settings.h,
+            x: settings.x,
+            y: settings.y,
+            vsync: settings.vsync,
+            fullscreen,
+            mode_w: display_mode.map(|m| m.w),
+            mode_h: display_mode.map(|m| m.h),
+            refresh_rate: display_mode.map(|m| m.refresh_rate),
+        }
+    }
+}
+
+impl WindowSettings {
+    /// Loads TOML file. Returns default settings if the file doesn't exist
+    pub fn load(path: impl AsRef<Path>) -> AnfResult<Self> {
+        let path = path.as_ref();
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => return Err(err.into()),
+        };
+
+        toml::from_str(&text)
+            .map_err(|err| anyhow::anyhow!("failed to parse `{}`: {}", path.display(), err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> AnfResult<()> {
+        let text = toml::to_string_pretty(self)?;
+        fs::write(path, text)?;
+        Ok(())
+    }
+
+    /// Parses command line flags (without the program name). Unknown arguments are ignored
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> AnfResult<Self> {
+        let mut settings = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--width" => settings.w = Some(self::parse_value(&arg, args.next())?),
+                "--height" => settings.h = Some(self::parse_value(&arg, args.next())?),
+                "--fullscreen" => settings.fullscreen = Some(FullscreenMode::Borderless),
+                "--windowed" => settings.fullscreen = Some(FullscreenMode::Windowed),
+                "--vsync" => settings.vsync = Some(true),
+                "--no-vsync" => settings.vsync = Some(false),
+                _ => {}
+            }
+        }
+
+        Ok(settings)
+    }
+
+    /// Current state of the window. The size and the position are `None` unless the window is
+    /// windowed (so that fullscreen doesn't overwrite the windowed geometry)
+    pub fn from_window(win: &WindowHandle) -> Self {
+        let fullscreen = win.fullscreen_mode().ok();
+        let mut settings = Self {
+            vsync: win.vsync,
+            fullscreen,
+            ..Self::default()
+        };
+
+        if fullscreen == Some(FullscreenMode::Windowed) {
+            let (w, h) = win.screen_size();
+            let (x, y) = win.position();
+            settings.w = Some(w);
+            settings.h = Some(h);
+            settings.x = Some(x);
+            settings.y = Some(y);
+        }
+
+        settings
+    }
+
+    /// Overwrites fields with `Some` fields of `other`
+    pub fn merge(&mut self, other: &Self) {
+        self.w = other.w.or(self.w);
+        self.h = other.h.or(self.h);
+        self.x = other.x.or(self.x);
+        self.y = other.y.or(self.y);
+        self.vsync = other.vsync.or(self.vsync);
+        self.fullscreen = other.fullscreen.or(self.fullscreen);
+    }
+
+    pub fn apply(&self, cfg: &mut WindowConfig) {
+        if let Some(w) = self.w {
+            cfg.w = w;
+        }
+        if let Some(h) = self.h {
+            cfg.h = h;
+        }
+        if let (Some(x), Some(y)) = (self.x, self.y) {
+            cfg.position = Some([x, y]);
+        }
+        if let Some(fullscreen) = self.fullscreen {
+            cfg.fullscreen = fullscreen;
+        }
+        if let Some(vsync) = self.vsync {
+            cfg.present_interval = if vsync {
+                PresentInterval::One
+            } else {
+                PresentInterval::Immediate
+            };
+        }
+    }
+}
+
+fn parse_value<T: std::str::FromStr>(flag: &str, value: Option<String>) -> AnfResult<T> {
+    value
+        .and_then(|v| v.parse().ok())
+        .ok_or_else(|| anyhow::anyhow!("`{}` requires a valid value", flag))
+}
+
+impl WindowConfig {
+    /// Overrides the config with the settings file, and saves the window state to the file on
+    /// exit
+    pub fn load_settings(&mut self, path: impl AsRef<Path>) -> AnfResult<()> {
+        self.load_settings_with_args(path, std::iter::empty())
+    }
+
+    /// Overrides the config with the settings file and then command line arguments (without the
+    /// program name), and saves the window state to the file on exit
+    pub fn load_settings_with_args(
+        &mut self,
+        path: impl AsRef<Path>,
+        args: impl IntoIterator<Item = String>,
+    ) -> AnfResult<()> {
+        let path = path.as_ref();
+
+        let mut settings = WindowSettings::load(path)?;
+        settings.merge(&WindowSettings::from_args(args)?);
+        settings.apply(self);
+
+        self.settings_path = Some(path.to_path_buf());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(xs: &[&str]) -> Vec<String> {
+        xs.iter().map(|x| x.to_string()).collect()
+    }
+
+    #[test]
+    fn test_args() {
+        let settings =
+            WindowSettings::from_args(args(&["--width", "640", "--fullscreen", "--no-vsync"]))
+                .unwrap();
+        assert_eq!(settings.w, Some(640));
+        assert_eq!(settings.h, None);
+        assert_eq!(settings.fullscreen, Some(FullscreenMode::Borderless));
+        assert_eq!(settings.vsync, Some(false));
+
+        assert!(WindowSettings::from_args(args(&["--width"])).is_err());
+        assert!(WindowSettings::from_args(args(&["--width", "wide"])).is_err());
+    }
+
+    #[test]
+    fn test_merge_and_toml() {
+        let mut settings: WindowSettings =
+            toml::from_str("w = 320\nh = 180\nvsync = true").unwrap();
+        settings.merge(&WindowSettings {
+            w: Some(640),
+            vsync: Some(false),
+            ..Default::default()
+        });
+
+        assert_eq!(settings.w, Some(640));
+        assert_eq!(settings.h, Some(180));
+        assert_eq!(settings.vsync, Some(false));
+
+        let text = toml::to_string_pretty(&settings).unwrap();
+        assert_eq!(toml::from_str::<WindowSettings>(&text).unwrap(), settings);
+    }
+
+    #[test]
+    fn test_exclusive_toml() {
+        let mode = DisplayMode {
+            w: 1920,
+            h: 1080,
+            refresh_rate: 60,
+        };
+
+        for fullscreen in &[
+            FullscreenMode::Exclusive(Some(mode)),
+            FullscreenMode::Exclusive(None),
+            FullscreenMode::Borderless,
+        ] {
+            let settings = WindowSettings {
+                w: Some(1280),
+                fullscreen: Some(*fullscreen),
+                ..Default::default()
+            };
+
+            let text = toml::to_string_pretty(&settings).unwrap();
+            assert_eq!(toml::from_str::<WindowSettings>(&text).unwrap(), settings);
+        }
+
+        let settings: WindowSettings =
+            toml::from_str("fullscreen = \"exclusive\"\nmode_w = 800\nmode_h = 600").unwrap();
+        assert_eq!(
+            settings.fullscreen,
+            Some(FullscreenMode::Exclusive(Some(DisplayMode {
+                w: 800,
+                h: 600,
+                refresh_rate: 0,
+            })))
+        );
+    }
+
+    #[test]
+    fn test_load_with_args() {
+        let path = std::env::temp_dir().join("anf_test_load_with_args.toml");
+        fs::write(&path, "w = 320\nh = 180\nfullscreen = \"borderless\"").unwrap();
+
+        let mut cfg = WindowConfig::default();
+        cfg.load_settings_with_args(&path, args(&["--width", "640", "--windowed"]))
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!([cfg.w, cfg.h], [640, 180]);
+        assert_eq!(cfg.fullscreen, FullscreenMode::Windowed);
+        assert_eq!(cfg.settings_path, Some(path));
+    }
+}
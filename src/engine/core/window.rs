@@ -1,24 +1,66 @@
 //! Creation of SDL window and FNA3D device
 
 use {
-    fna3h::{win::PresentationParameters, Device},
+    fna3h::{win::PresentInterval, DepthFormat, SurfaceFormat},
     sdl2::{
+        event::Event,
+        mouse::{Cursor, SystemCursor},
+        pixels::PixelFormatEnum,
+        rect::Rect,
+        surface::Surface,
         sys::SDL_Window,
-        video::{FullscreenType, WindowPos},
+        video::{self, FullscreenType, VideoSubsystem, WindowPos},
         EventPump, IntegerOrSdlError,
     },
-    std::ffi::NulError,
+    std::{
+        ffi::NulError,
+        fs,
+        io::{Read, Seek},
+        path::Path,
+        rc::Rc,
+    },
+};
+
+#[cfg(feature = "settings")]
+use {crate::engine::core::settings::WindowSettings, std::path::PathBuf};
+
+use crate::engine::{
+    core::{
+        clock::TargetFps,
+        lifecycle::{self, AnfResult, Backbuffer, Backbuffers},
+    },
+    draw::DrawContext,
 };
 
 /// Initial settings of the window
 pub struct WindowConfig {
     pub title: String,
+    /// Window width in points
     pub w: u32,
+    /// Window height in points
     pub h: u32,
+    /// Window position. Centered if `None`
+    pub position: Option<[i32; 2]>,
+    pub fullscreen: FullscreenMode,
     pub is_debug: bool,
     pub rm_decoration: bool,
     pub allow_high_dpi: bool,
     pub is_resizable: bool,
+    /// Initial timestep mode of the game loop. It can be changed via `GameClock` at runtime
+    pub target_fps: TargetFps,
+    /// Presentation interval. `PresentInterval::Immediate` disables vsync
+    pub present_interval: PresentInterval,
+    /// Number of samples for multisample anti-aliasing (`0` to disable)
+    pub multisample_count: u32,
+    pub depth_stencil_format: DepthFormat,
+    pub backbuffer_format: SurfaceFormat,
+    /// If true, the framework presents the backbuffer after `render`. Set `false` to present
+    /// yourself
+    pub auto_present: bool,
+    /// Settings file to save the window state on exit. Set with [`WindowConfig::load_settings`]
+    /// or [`WindowConfig::load_settings_with_args`]
+    #[cfg(feature = "settings")]
+    pub settings_path: Option<PathBuf>,
 }
 
 impl Default for WindowConfig {
@@ -27,20 +69,207 @@ impl Default for WindowConfig {
             title: "† ANF game †".to_string(),
             w: 1280,
             h: 720,
+            position: None,
+            fullscreen: FullscreenMode::Windowed,
             is_debug: true,
             rm_decoration: false,
             allow_high_dpi: true,
             is_resizable: false,
+            target_fps: TargetFps::default(),
+            present_interval: PresentInterval::Default,
+            multisample_count: 0,
+            depth_stencil_format: DepthFormat::None,
+            backbuffer_format: SurfaceFormat::Color,
+            auto_present: true,
+            #[cfg(feature = "settings")]
+            settings_path: None,
         }
     }
 }
 
+/// Fullscreen state of the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FullscreenMode {
+    Windowed,
+    /// Fullscreen window with the desktop resolution (doesn't change the display mode)
+    Borderless,
+    /// Changes the display mode. The closest mode supported by the display is used. `None`
+    /// keeps the current display mode of the window
+    Exclusive(Option<DisplayMode>),
+}
+
+/// Resolution and refresh rate of a display
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct DisplayMode {
+    pub w: u32,
+    pub h: u32,
+    /// Refresh rate in Hz (`0` if unspecified)
+    pub refresh_rate: u32,
+}
+
+impl DisplayMode {
+    fn from_sdl(mode: video::DisplayMode) -> Self {
+        Self {
+            w: mode.w as u32,
+            h: mode.h as u32,
+            refresh_rate: mode.refresh_rate as u32,
+        }
+    }
+
+    fn to_sdl(&self) -> video::DisplayMode {
+        video::DisplayMode::new(
+            sdl2::pixels::PixelFormatEnum::Unknown,
+            self.w as i32,
+            self.h as i32,
+            self.refresh_rate as i32,
+        )
+    }
+}
+
+/// Connected display
+#[derive(Debug, Clone)]
+pub struct DisplayInfo {
+    pub index: i32,
+    pub name: String,
+    /// Position and size in the desktop
+    pub bounds: Rect,
+    pub desktop_mode: DisplayMode,
+    /// Supported modes (from the largest to the smallest)
+    pub modes: Vec<DisplayMode>,
+}
+
+impl DisplayInfo {
+    fn query(video: &VideoSubsystem, index: i32) -> Result<Self, String> {
+        let mut modes = Vec::new();
+        for i in 0..video.num_display_modes(index)? {
+            let mode = DisplayMode::from_sdl(video.display_mode(index, i)?);
+            // the same mode can be listed for each pixel format
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
+        }
+
+        Ok(Self {
+            index,
+            name: video.display_name(index)?,
+            bounds: video.display_bounds(index)?,
+            desktop_mode: DisplayMode::from_sdl(video.desktop_display_mode(index)?),
+            modes,
+        })
+    }
+}
+
+/// Decoded RGBA8 pixels for window icons, cursors and screenshots
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub pixels: Vec<u8>,
+    pub w: u32,
+    pub h: u32,
+}
+
+impl RgbaImage {
+    pub fn new(pixels: Vec<u8>, w: u32, h: u32) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (w * h * 4) as usize,
+            "pixels must be RGBA8 of size {}x{}",
+            w,
+            h
+        );
+        Self { pixels, w, h }
+    }
+
+    /// Loads PNG (or other format supported by FNA3D) file
+    pub fn from_path(path: impl AsRef<Path>) -> AnfResult<Self> {
+        let path = path.as_ref();
+        let bytes = fs::read(path)?;
+        Self::from_encoded_bytes(&bytes)
+            .ok_or_else(|| anyhow::anyhow!("failed to decode image `{}`", path.display()))
+    }
+
+    /// Helper for embedded file bytes
+    pub fn from_encoded_bytes(bytes: &[u8]) -> Option<Self> {
+        Self::from_reader(std::io::Cursor::new(bytes))
+    }
+
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Option<Self> {
+        let (pixels_ptr, len, [w, h]) = fna3h::img::from_reader(reader, None);
+
+        if pixels_ptr == std::ptr::null_mut() {
+            return None;
+        }
+
+        let pixels = unsafe { std::slice::from_raw_parts(pixels_ptr, len as usize) }.to_vec();
+        fna3h::img::free(pixels_ptr as *mut _);
+
+        Some(Self { pixels, w, h })
+    }
+
+    pub fn encode_png(&self) -> std::io::Result<Vec<u8>> {
+        crate::gfx::encode_png(&self.pixels, self.w, self.h)
+    }
+
+    /// Saves the image as PNG, creating parent directories
+    pub fn save_png(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(path, self.encode_png()?)
+    }
+
+    /// SDL copies the pixels when making icons or cursors, so the surface is temporary
+    fn with_surface<T>(&self, f: impl FnOnce(&Surface) -> T) -> Result<T, String> {
+        let mut pixels = self.pixels.clone();
+        // RGBA8 in byte order
+        let surface = Surface::from_data(
+            &mut pixels,
+            self.w,
+            self.h,
+            self.w * 4,
+            PixelFormatEnum::RGBA32,
+        )?;
+        Ok(f(&surface))
+    }
+}
+
 /// Owner of SDL2 window
 ///
 /// The window is dropped when this handle goes out of scope.
 pub struct WindowHandle {
     pub sdl: sdl2::Sdl,
     pub win: sdl2::video::Window,
+    /// Set when the `DrawContext` is created so that mode changes reset the backbuffer
+    pub(crate) backbuffer: Option<Rc<Backbuffer>>,
+    /// Backbuffers of all the windows, shared with `AnfFramework`
+    pub(crate) backbuffers: Rc<Backbuffers>,
+    /// SDL doesn't own cursors, so we keep the current one alive
+    cursor: Option<Cursor>,
+    /// Where to save the window state on drop
+    #[cfg(feature = "settings")]
+    settings_path: Option<PathBuf>,
+    /// Saved to the settings file (`None` if the presentation interval is not explicit)
+    #[cfg(feature = "settings")]
+    pub(crate) vsync: Option<bool>,
+}
+
+/// Saves the window state to the settings file
+///
+/// The size and position in the file are kept if the window is fullscreen.
+#[cfg(feature = "settings")]
+impl Drop for WindowHandle {
+    fn drop(&mut self) {
+        let path = match self.settings_path.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+
+        let mut settings = WindowSettings::load(path).unwrap_or_default();
+        settings.merge(&WindowSettings::from_window(self));
+        if let Err(err) = settings.save(path) {
+            log::warn!("failed to save window settings: {}", err);
+        }
+    }
 }
 
 impl AsRef<sdl2::video::Window> for WindowHandle {
@@ -50,38 +279,80 @@ impl AsRef<sdl2::video::Window> for WindowHandle {
 }
 
 // TODOs:
-// set icon: https://github.com/FNA-XNA/FNA/blob/master/src/FNAPlatform/SDL2_FNAPlatform.cs#L534
 // display orientation
 impl WindowHandle {
     pub fn from_cfg(cfg: &WindowConfig) -> Self {
-        let flags = fna3h::win::prepare_window_attributes();
         let sdl = sdl2::init().unwrap();
+        Self::with_sdl(sdl, cfg, Rc::new(Backbuffers::default()))
+    }
+
+    fn with_sdl(sdl: sdl2::Sdl, cfg: &WindowConfig, backbuffers: Rc<Backbuffers>) -> Self {
+        let flags = fna3h::win::prepare_window_attributes();
         let win = self::create_sdl_window(cfg, &sdl, flags.0);
 
-        WindowHandle { sdl, win }
+        let mut handle = WindowHandle {
+            sdl,
+            win,
+            backbuffer: None,
+            backbuffers,
+            cursor: None,
+            #[cfg(feature = "settings")]
+            settings_path: cfg.settings_path.clone(),
+            #[cfg(feature = "settings")]
+            vsync: match cfg.present_interval {
+                PresentInterval::One => Some(true),
+                PresentInterval::Immediate => Some(false),
+                _ => None,
+            },
+        };
+
+        if cfg.fullscreen != FullscreenMode::Windowed {
+            if let Err(err) = handle.set_fullscreen(cfg.fullscreen) {
+                log::warn!("failed to make the window fullscreen: {}", err);
+            }
+        }
+
+        handle
     }
 
     pub fn raw_window(&self) -> *mut SDL_Window {
         self.win.raw()
     }
 
+    /// Window ID used to route events
+    pub fn id(&self) -> u32 {
+        self.win.id()
+    }
+
+    /// If the event is sent to this window
+    pub fn is_event_target(&self, ev: &Event) -> bool {
+        ev.get_window_id() == Some(self.id())
+    }
+
+    /// Opens another window with it's own FNA3D device and [`DrawContext`]
+    ///
+    /// The framework resizes and presents the backbuffer of the window until the handle is
+    /// dropped. Use [`WindowHandle::is_event_target`] to route events to the windows.
+    pub fn open_window(&self, cfg: &WindowConfig) -> (WindowHandle, DrawContext) {
+        let mut win = Self::with_sdl(self.sdl.clone(), cfg, Rc::clone(&self.backbuffers));
+        let dcx = lifecycle::create_draw_context(&mut win, cfg);
+        (win, dcx)
+    }
+
     pub(crate) fn event_pump(&mut self) -> Result<EventPump, String> {
         self.sdl.event_pump()
     }
 
+    /// Window size in points. See also [`WindowHandle::drawable_size`]
     pub fn screen_size(&self) -> (u32, u32) {
         self.win.size()
     }
-    pub fn set_screen_size(
-        &mut self,
-        size: [u32; 2],
-        device: &Device,
-        params: &mut PresentationParameters,
-    ) {
-        self.win.set_size(size[0], size[1]).unwrap();
-        params.backBufferWidth = size[0] as i32;
-        params.backBufferHeight = size[1] as i32;
-        device.reset_backbuffer(&params);
+    /// Resizes the window
+    ///
+    /// `AnfFramework` resizes the backbuffer, the viewport and the projection matrix of
+    /// `DrawContext` when it receives the resize event.
+    pub fn set_screen_size(&mut self, size: [u32; 2]) -> Result<(), IntegerOrSdlError> {
+        self.win.set_size(size[0], size[1])
     }
 
     pub fn title(&self) -> &str {
@@ -130,10 +401,201 @@ impl WindowHandle {
     }
 }
 
+/// High-DPI
+impl WindowHandle {
+    /// Window size in pixels, i.e. the backbuffer size
+    pub fn drawable_size(&self) -> (u32, u32) {
+        let [w, h] = self::raw_drawable_size(self.win.raw());
+        (w, h)
+    }
+
+    /// Pixels per point (e.g. `2.0` on retina displays if `allow_high_dpi` is set)
+    pub fn dpi_scale(&self) -> f32 {
+        self::dpi_scale(self.win.raw())
+    }
+
+    /// Converts window coordinates (e.g. mouse position) to backbuffer pixels
+    pub fn points_to_pixels(&self, pos: [f32; 2]) -> [f32; 2] {
+        let scale = self.dpi_scale();
+        [pos[0] * scale, pos[1] * scale]
+    }
+
+    pub fn pixels_to_points(&self, pos: [f32; 2]) -> [f32; 2] {
+        let scale = self.dpi_scale();
+        [pos[0] / scale, pos[1] / scale]
+    }
+}
+
+/// OpenGL context of a FNA3D device (null with other backends)
+///
+/// FNA3D doesn't switch contexts, so we make the context current before using the device.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct DeviceContext {
+    window: *mut SDL_Window,
+    gl: sdl2::sys::SDL_GLContext,
+}
+
+impl DeviceContext {
+    /// Context of the device that is created last
+    pub(crate) fn current(window: *mut SDL_Window) -> Self {
+        Self {
+            window,
+            gl: unsafe { sdl2::sys::SDL_GL_GetCurrentContext() },
+        }
+    }
+
+    pub(crate) fn make_current(&self) {
+        unsafe {
+            if !self.gl.is_null() && sdl2::sys::SDL_GL_GetCurrentContext() != self.gl {
+                sdl2::sys::SDL_GL_MakeCurrent(self.window, self.gl);
+            }
+        }
+    }
+}
+
+/// Size of the window in pixels
+///
+/// Queried through FNA3D so that it works with any backend (`SDL_GL_GetDrawableSize` returns the
+/// size in points unless the window is for OpenGL).
+pub(crate) fn raw_drawable_size(win: *mut SDL_Window) -> [u32; 2] {
+    let (w, h) = fna3h::win::get_drawable_size(win as *mut _);
+    [w as u32, h as u32]
+}
+
+/// Pixels per point of the window
+pub(crate) fn dpi_scale(win: *mut SDL_Window) -> f32 {
+    let (mut w, mut h) = (0, 0);
+    unsafe {
+        sdl2::sys::SDL_GetWindowSize(win, &mut w, &mut h);
+    }
+    if w <= 0 {
+        return 1.0;
+    }
+    self::raw_drawable_size(win)[0] as f32 / w as f32
+}
+
+/// Fullscreen and display modes
+impl WindowHandle {
+    pub fn fullscreen_mode(&self) -> Result<FullscreenMode, String> {
+        Ok(match self.win.fullscreen_state() {
+            FullscreenType::Off => FullscreenMode::Windowed,
+            FullscreenType::Desktop => FullscreenMode::Borderless,
+            FullscreenType::True => {
+                FullscreenMode::Exclusive(Some(DisplayMode::from_sdl(self.win.display_mode()?)))
+            }
+        })
+    }
+
+    /// Switches fullscreen state and resets the backbuffer to the new window size
+    pub fn set_fullscreen(&mut self, mode: FullscreenMode) -> Result<(), String> {
+        match mode {
+            FullscreenMode::Windowed => self.win.set_fullscreen(FullscreenType::Off)?,
+            FullscreenMode::Borderless => self.win.set_fullscreen(FullscreenType::Desktop)?,
+            FullscreenMode::Exclusive(display_mode) => {
+                if let Some(display_mode) = display_mode {
+                    let index = self.win.display_index()?;
+                    let closest = self
+                        .win
+                        .subsystem()
+                        .closest_display_mode(index, &display_mode.to_sdl())?;
+                    self.win.set_display_mode(closest)?;
+                }
+                self.win.set_fullscreen(FullscreenType::True)?;
+            }
+        }
+
+        self.reset_backbuffer();
+        Ok(())
+    }
+
+    /// Index of the display that contains the center of the window
+    pub fn display_index(&self) -> Result<i32, String> {
+        self.win.display_index()
+    }
+
+    /// Enumerates connected displays and their modes
+    pub fn displays(&self) -> Result<Vec<DisplayInfo>, String> {
+        let video = self.win.subsystem();
+        (0..video.num_video_displays()?)
+            .map(|i| DisplayInfo::query(video, i))
+            .collect()
+    }
+
+    /// Resizes the backbuffer to the drawable size of the window
+    ///
+    /// Resize events also do this, but they're not always sent on mode changes.
+    fn reset_backbuffer(&self) {
+        if let Some(backbuffer) = self.backbuffer.as_ref() {
+            backbuffer.sync();
+        }
+    }
+}
+
+/// Icon and mouse cursor
+impl WindowHandle {
+    pub fn set_icon(&mut self, icon: &RgbaImage) -> Result<(), String> {
+        let win = &mut self.win;
+        icon.with_surface(|surface| win.set_icon(surface))
+    }
+
+    /// Sets the window icon from a PNG file
+    pub fn set_icon_from_path(&mut self, path: impl AsRef<Path>) -> AnfResult<()> {
+        let icon = RgbaImage::from_path(path)?;
+        self.set_icon(&icon).map_err(anyhow::Error::msg)
+    }
+
+    /// Sets a hardware cursor. `hotspot` is the clicking point in pixels from the top-left
+    pub fn set_cursor(&mut self, image: &RgbaImage, hotspot: [i32; 2]) -> Result<(), String> {
+        let cursor = image
+            .with_surface(|surface| Cursor::from_surface(surface, hotspot[0], hotspot[1]))??;
+        self.apply_cursor(cursor);
+        Ok(())
+    }
+
+    pub fn set_system_cursor(&mut self, cursor: SystemCursor) -> Result<(), String> {
+        let cursor = Cursor::from_system(cursor)?;
+        self.apply_cursor(cursor);
+        Ok(())
+    }
+
+    fn apply_cursor(&mut self, cursor: Cursor) {
+        cursor.set();
+        // the previous cursor is freed after the new one is set
+        self.cursor = Some(cursor);
+    }
+
+    pub fn is_cursor_visible(&self) -> bool {
+        self.sdl.mouse().is_cursor_showing()
+    }
+    pub fn set_cursor_visible(&mut self, is_visible: bool) {
+        self.sdl.mouse().show_cursor(is_visible);
+    }
+
+    /// If the mouse is in relative mode (hidden, reporting only motion; for FPS camera etc.)
+    pub fn is_relative_mouse(&self) -> bool {
+        self.sdl.mouse().relative_mouse_mode()
+    }
+    pub fn set_relative_mouse(&mut self, is_relative: bool) {
+        self.sdl.mouse().set_relative_mouse_mode(is_relative);
+    }
+
+    /// If the mouse is confined to the window
+    pub fn is_mouse_captured(&self) -> bool {
+        self.win.grab()
+    }
+    pub fn set_mouse_captured(&mut self, is_captured: bool) {
+        self.win.set_grab(is_captured);
+    }
+}
+
 fn create_sdl_window(cfg: &WindowConfig, sdl: &sdl2::Sdl, flags: u32) -> sdl2::video::Window {
     let video = sdl.video().unwrap();
     let mut window = video.window(&cfg.title, cfg.w, cfg.h);
-    window.set_window_flags(flags).position_centered();
+    window.set_window_flags(flags);
+    match cfg.position {
+        Some([x, y]) => window.position(x, y),
+        None => window.position_centered(),
+    };
     if cfg.rm_decoration {
         window.borderless();
     }
@@ -17,13 +17,81 @@ use {
         geom2d::*,
     },
     fna3d_hie::Pipeline,
-    fna3h::{self, tex::Texture, win::PresentationParameters, Color, Device},
-    std::time::Duration,
+    fna3h::{
+        draw::{pip::RasterizerState, Rect, Viewport},
+        tex::Texture,
+        win::PresentationParameters,
+        Color, Device,
+    },
+    std::{
+        cell::{Cell, RefCell},
+        path::PathBuf,
+        rc::Rc,
+        time::Duration,
+    },
+};
+
+use crate::{
+    engine::core::window::{DeviceContext, RgbaImage},
+    gfx::{geom3d::Mat4x4, Camera2d, ClearCommand, RenderTarget2d, TextureData2d},
 };
 
-use crate::gfx::TextureData2d;
+/// How [`VirtualResolution`] is scaled to the backbuffer
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ScalePolicy {
+    /// Fills the backbuffer ignoring the aspect ratio
+    Stretch,
+    /// Keeps the aspect ratio, adding bars to the sides
+    Fit,
+    /// Scales only by integer factors (pixel-perfect), adding bars to the sides
+    Integer,
+}
+
+/// Fixed logical screen size scaled to the backbuffer
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VirtualResolution {
+    /// Logical size in pixels
+    pub size: [u32; 2],
+    pub policy: ScalePolicy,
+    /// Color of the letterbox bars
+    pub bar_color: Color,
+}
+
+impl VirtualResolution {
+    /// Fit policy with black bars
+    pub fn new(w: u32, h: u32) -> Self {
+        Self {
+            size: [w, h],
+            policy: ScalePolicy::Fit,
+            bar_color: Color::rgba(0, 0, 0, 255),
+        }
+    }
+
+    /// Viewport `[x, y, w, h]` in the backbuffer of size `[bw, bh]`
+    pub fn viewport(&self, backbuffer: [u32; 2]) -> [i32; 4] {
+        let [lw, lh] = self.size;
+        let [bw, bh] = backbuffer;
+        if lw == 0 || lh == 0 {
+            return [0, 0, bw as i32, bh as i32];
+        }
 
-static mut WHITE_DOT: Option<TextureData2d> = None;
+        let scale = (bw as f32 / lw as f32).min(bh as f32 / lh as f32);
+        let [w, h] = match self.policy {
+            ScalePolicy::Stretch => return [0, 0, bw as i32, bh as i32],
+            ScalePolicy::Fit => [
+                (lw as f32 * scale).round() as i32,
+                (lh as f32 * scale).round() as i32,
+            ],
+            ScalePolicy::Integer => {
+                // shows at least the logical size even if the window is smaller
+                let scale = (scale.floor() as i32).max(1);
+                [lw as i32 * scale, lh as i32 * scale]
+            }
+        };
+
+        [(bw as i32 - w) / 2, (bh as i32 - h) / 2, w, h]
+    }
+}
 
 /// The imperative draw API
 ///
@@ -36,12 +104,41 @@ pub struct DrawContext {
     push: QuadParams,
     /// Dependency
     device: Device,
+    /// Made current before drawing so that each window can have it's own `DrawContext`
+    context: DeviceContext,
+    /// Used to draw lines and rectangles
+    white_dot: TextureData2d,
     /// Dependency
     params: PresentationParameters,
+    /// Backbuffer size updated by the framework on resize
+    backbuffer_size: Rc<Cell<[u32; 2]>>,
+    /// Screenshots saved by the framework before presenting
+    screenshot_requests: Rc<RefCell<Vec<PathBuf>>>,
+    virtual_res: Option<VirtualResolution>,
+    /// Current viewport `[x, y, w, h]` in the backbuffer
+    viewport: [i32; 4],
+    /// Current size of the projection matrix
+    proj_size: [u32; 2],
+    /// Bound render targets (the last one is drawn to)
+    targets: Vec<TargetFrame>,
+    /// Clip rectangles in logical coordinates (the last one is applied as the scissor rectangle)
+    clips: Vec<Rect2f>,
+    /// If the scissor test is enabled in the rasterizer state
+    is_scissor_enabled: bool,
     /// Interface
     dt: Duration,
 }
 
+/// Render target pushed to [`DrawContext`] with the states to restore on pop
+#[derive(Debug)]
+struct TargetFrame {
+    target: RenderTarget2d,
+    viewport: [i32; 4],
+    proj_size: [u32; 2],
+    transform: Mat4x4,
+    clips: Vec<Rect2f>,
+}
+
 impl DrawContext {
     pub fn new(
         mut device: Device,
@@ -49,25 +146,168 @@ impl DrawContext {
         params: PresentationParameters,
     ) -> Self {
         let pipe = Pipeline::new(&mut device, ColoredVertexData::decl(), default_shader_bytes);
-        let batcher = Batcher::from_device(&mut device);
+        let mut batcher = Batcher::from_device(&mut device);
+        batcher.set_screen_size(
+            params.backBufferWidth as u32,
+            params.backBufferHeight as u32,
+        );
 
-        unsafe {
-            let white_dot =
-                TextureData2d::from_encoded_bytes(&device, crate::engine::embedded::WHITE_DOT)
-                    .unwrap();
-            WHITE_DOT = Some(white_dot);
-        }
+        let white_dot =
+            TextureData2d::from_encoded_bytes(&device, crate::engine::embedded::WHITE_DOT).unwrap();
 
         Self {
+            // the device is current right after it's created
+            context: DeviceContext::current(params.deviceWindowHandle as *mut _),
+            white_dot,
             device,
             batcher,
             pipe,
             push: QuadParams::default(),
+            backbuffer_size: Rc::new(Cell::new([
+                params.backBufferWidth as u32,
+                params.backBufferHeight as u32,
+            ])),
+            screenshot_requests: Rc::new(RefCell::new(Vec::new())),
+            virtual_res: None,
+            viewport: [
+                0,
+                0,
+                params.backBufferWidth as i32,
+                params.backBufferHeight as i32,
+            ],
+            proj_size: [
+                params.backBufferWidth as u32,
+                params.backBufferHeight as u32,
+            ],
+            targets: Vec::new(),
+            clips: Vec::new(),
+            is_scissor_enabled: false,
             params,
             dt: Duration::default(),
         }
     }
 
+    pub(crate) fn shared_backbuffer_size(&self) -> Rc<Cell<[u32; 2]>> {
+        Rc::clone(&self.backbuffer_size)
+    }
+
+    pub(crate) fn shared_screenshot_requests(&self) -> Rc<RefCell<Vec<PathBuf>>> {
+        Rc::clone(&self.screenshot_requests)
+    }
+
+    /// Applies backbuffer resize (or virtual resolution) to the parameters, the viewport and the
+    /// projection matrix
+    ///
+    /// It's delayed while render targets are pushed.
+    fn sync_viewport(&mut self) {
+        if !self.targets.is_empty() {
+            return;
+        }
+
+        let [w, h] = self.backbuffer_size.get();
+        let viewport = self.target_viewport();
+        if [w as i32, h as i32] == [self.params.backBufferWidth, self.params.backBufferHeight]
+            && viewport == self.viewport
+            && self.proj_size == self.logical_size()
+        {
+            return;
+        }
+
+        self.flush();
+
+        self.params.backBufferWidth = w as i32;
+        self.params.backBufferHeight = h as i32;
+
+        let [lw, lh] = self.logical_size();
+        self.apply_viewport(viewport, [lw, lh]);
+        // the scissor rectangle is in pixels
+        self.apply_clip();
+    }
+
+    /// Viewport in the backbuffer for the current backbuffer size
+    fn target_viewport(&self) -> [i32; 4] {
+        let size = self.backbuffer_size.get();
+        match self.virtual_res {
+            Some(res) => res.viewport(size),
+            None => [0, 0, size[0] as i32, size[1] as i32],
+        }
+    }
+
+    fn logical_size(&self) -> [u32; 2] {
+        match self.virtual_res {
+            Some(res) => res.size,
+            None => self.backbuffer_size.get(),
+        }
+    }
+
+    fn apply_viewport(&mut self, viewport: [i32; 4], proj_size: [u32; 2]) {
+        let [x, y, w, h] = viewport;
+        self.device.set_viewport(&Viewport {
+            x,
+            y,
+            w,
+            h,
+            minDepth: 0.0,
+            maxDepth: 1.0,
+        });
+        self.batcher.set_screen_size(proj_size[0], proj_size[1]);
+        self.viewport = viewport;
+        self.proj_size = proj_size;
+    }
+
+    /// Fills the outside of the virtual screen with the bar color
+    fn draw_bars(&mut self) {
+        if !self.targets.is_empty() {
+            return;
+        }
+
+        let res = match self.virtual_res {
+            Some(res) if res.policy != ScalePolicy::Stretch => res,
+            _ => return,
+        };
+
+        let [bw, bh] = self.backbuffer_size.get();
+        let [bw, bh] = [bw as i32, bh as i32];
+        let [x, y, w, h] = self.viewport;
+        let bars = [
+            [0, 0, x, bh],
+            [x + w, 0, bw - (x + w), bh],
+            [x, 0, w, y],
+            [x, y + h, w, bh - (y + h)],
+        ];
+        if bars.iter().all(|b| b[2] <= 0 || b[3] <= 0) {
+            return;
+        }
+
+        self.flush();
+        let (viewport, opacity) = (self.viewport, self.batcher.opacity());
+        self.apply_viewport([0, 0, bw, bh], [bw as u32, bh as u32]);
+        self.batcher.set_opacity(1.0);
+
+        let white_dot = self.white_dot.clone();
+        for bar in bars.iter().filter(|b| b[2] > 0 && b[3] > 0) {
+            if self.batcher.is_satured() {
+                self.flush();
+            }
+            let quad = QuadPush {
+                params: &mut self.push,
+                target: self.batcher.next_quad_mut(
+                    white_dot.raw_texture(),
+                    &self.device,
+                    &mut self.pipe,
+                ),
+            };
+            SpritePush::new(quad, &white_dot)
+                .color(res.bar_color)
+                .dest_rect_px([bar[0] as f32, bar[1] as f32, bar[2] as f32, bar[3] as f32]);
+        }
+
+        self.flush();
+        self.batcher.set_opacity(opacity);
+        let proj_size = self.logical_size();
+        self.apply_viewport(viewport, proj_size);
+    }
+
     pub fn raw_window(&self) -> *mut sdl2::sys::SDL_Window {
         self.params.deviceWindowHandle as *mut _
     }
@@ -75,7 +315,9 @@ impl DrawContext {
 
 /// Context
 impl DrawContext {
+    /// Makes the device current and returns it
     pub fn device(&mut self) -> &Device {
+        self.context.make_current();
         &self.device
     }
 
@@ -85,15 +327,254 @@ impl DrawContext {
     }
 }
 
+/// Render target stack
+impl DrawContext {
+    /// Draws to the render target until it's popped. Flushes the batcher
+    ///
+    /// The viewport and the projection matrix are set to the render target size and the transform
+    /// and the clip stack are reset. The current ones are restored on pop.
+    /// Prefer [`DrawContext::offscreen`], which pops automatically.
+    pub fn push_render_target(&mut self, target: &RenderTarget2d) {
+        self.context.make_current();
+        // apply pending resize so that we restore the right viewport
+        self.sync_viewport();
+        self.flush();
+
+        self.targets.push(TargetFrame {
+            target: target.clone(),
+            viewport: self.viewport,
+            proj_size: self.proj_size,
+            transform: self.batcher.transform().clone(),
+            clips: std::mem::take(&mut self.clips),
+        });
+
+        target.bind(&self.device);
+        let [w, h] = target.size_px();
+        self.apply_viewport([0, 0, w as i32, h as i32], [w, h]);
+        self.batcher.set_transform(Mat4x4::identity());
+        self.apply_clip();
+    }
+
+    /// Restores the previous render target (or the backbuffer). Flushes the batcher
+    pub fn pop_render_target(&mut self) -> Option<RenderTarget2d> {
+        self.context.make_current();
+        self.flush();
+
+        let frame = self.targets.pop()?;
+        match self.targets.last() {
+            Some(prev) => prev.target.bind(&self.device),
+            None => RenderTarget2d::unbind(&self.device),
+        }
+        self.apply_viewport(frame.viewport, frame.proj_size);
+        self.batcher.set_transform(frame.transform);
+        self.clips = frame.clips;
+        self.apply_clip();
+
+        // the backbuffer might be resized while drawing offscreen
+        self.sync_viewport();
+
+        Some(frame.target)
+    }
+
+    /// Current render target. `None` if drawing to the backbuffer
+    pub fn render_target(&self) -> Option<&RenderTarget2d> {
+        self.targets.last().map(|f| &f.target)
+    }
+
+    /// Number of pushed render targets
+    pub fn n_render_targets(&self) -> usize {
+        self.targets.len()
+    }
+}
+
+/// Clip stack
+impl DrawContext {
+    /// Clips drawing to the rectangle (in logical coordinates, ignoring the transform) until it's
+    /// popped. Nested clips are intersected. Flushes the batcher
+    pub fn push_clip(&mut self, rect: impl Into<Rect2f>) {
+        let rect = rect.into();
+        let rect = match self.clips.last() {
+            Some(parent) => parent.intersection(&rect),
+            None => rect,
+        };
+
+        self.flush();
+        self.clips.push(rect);
+        self.apply_clip();
+    }
+
+    /// Restores the previous clip rectangle (or disables clipping). Flushes the batcher
+    pub fn pop_clip(&mut self) -> Option<Rect2f> {
+        let rect = self.clips.pop()?;
+        self.flush();
+        self.apply_clip();
+        Some(rect)
+    }
+
+    /// Replaces the clip stack with the scissor rectangle (or clears it with `None`). Flushes the
+    /// batcher
+    pub fn set_scissor(&mut self, rect: Option<Rect2f>) {
+        self.flush();
+        self.clips.clear();
+        self.clips.extend(rect);
+        self.apply_clip();
+    }
+
+    /// Current clip rectangle in logical coordinates
+    pub fn clip_rect(&self) -> Option<&Rect2f> {
+        self.clips.last()
+    }
+
+    /// Sets the scissor rectangle and the scissor test to the top of the clip stack
+    fn apply_clip(&mut self) {
+        let rect = match self.clips.last() {
+            Some(rect) => rect.clone(),
+            None => {
+                self.set_scissor_enabled(false);
+                return;
+            }
+        };
+
+        // logical coordinates to render target pixels
+        let [vx, vy, vw, vh] = self.viewport;
+        let [pw, ph] = self.proj_size;
+        let (sx, sy) = (vw as f32 / pw as f32, vh as f32 / ph as f32);
+        let x1 = vx + (rect.left() * sx).round() as i32;
+        let y1 = vy + (rect.top() * sy).round() as i32;
+        let x2 = vx + (rect.right() * sx).round() as i32;
+        let y2 = vy + (rect.bottom() * sy).round() as i32;
+
+        self.device.set_scissor_rect(&Rect {
+            x: x1,
+            y: y1,
+            w: (x2 - x1).max(0),
+            h: (y2 - y1).max(0),
+        });
+        self.set_scissor_enabled(true);
+    }
+
+    fn set_scissor_enabled(&mut self, enable: bool) {
+        if self.is_scissor_enabled == enable {
+            return;
+        }
+
+        let mut rst = RasterizerState::default();
+        rst.set_scissor_test_enable(enable);
+        self.device.apply_rasterizer_state(&rst);
+        self.is_scissor_enabled = enable;
+    }
+}
+
+/// Virtual resolution
+impl DrawContext {
+    pub fn virtual_resolution(&self) -> Option<&VirtualResolution> {
+        self.virtual_res.as_ref()
+    }
+
+    /// Renders to a fixed logical size scaled to the window. Flushes the batcher
+    ///
+    /// `None` renders to the backbuffer size.
+    pub fn set_virtual_resolution(&mut self, res: Option<VirtualResolution>) {
+        self.flush();
+        self.virtual_res = res;
+        self.sync_viewport();
+    }
+
+    /// Converts a position in the window (e.g. mouse position) in points to logical coordinates
+    pub fn to_logical(&self, pos: impl Into<Vec2f>) -> Vec2f {
+        let scale = crate::engine::core::window::dpi_scale(self.raw_window());
+        let pos = pos.into().scale([scale, scale]);
+        let [x, y, w, h] = self.target_viewport();
+        let [lw, lh] = self.logical_size();
+        if w <= 0 || h <= 0 {
+            return pos;
+        }
+        Vec2f::new(
+            (pos.x - x as f32) * lw as f32 / w as f32,
+            (pos.y - y as f32) * lh as f32 / h as f32,
+        )
+    }
+
+    /// Mouse position in logical coordinates
+    pub fn mouse_pos(&self) -> Vec2f {
+        let (mut x, mut y) = (0, 0);
+        unsafe {
+            sdl2::sys::SDL_GetMouseState(&mut x, &mut y);
+        }
+        self.to_logical([x as f32, y as f32])
+    }
+}
+
+/// Screenshot
+impl DrawContext {
+    /// Reads the backbuffer. Call it after drawing everything (e.g. at the end of `render`)
+    pub fn read_backbuffer(&mut self) -> RgbaImage {
+        self.flush();
+        self.context.make_current();
+        self::read_backbuffer(&self.device, self.backbuffer_size.get())
+    }
+
+    /// Saves the backbuffer as PNG when the frame is presented by the framework (so it contains
+    /// everything drawn in the frame)
+    pub fn request_screenshot(&mut self, path: impl Into<PathBuf>) {
+        self.screenshot_requests.borrow_mut().push(path.into());
+    }
+}
+
+/// Reads RGBA8 pixels of the backbuffer. VERY HEAVY task
+pub(crate) fn read_backbuffer(device: &Device, size: [u32; 2]) -> RgbaImage {
+    let [w, h] = size;
+    let mut pixels = vec![0; 4 * w as usize * h as usize];
+    device.read_backbuffer(0, 0, w, h, &mut pixels);
+    RgbaImage { pixels, w, h }
+}
+
 /// Batcher
 impl DrawContext {
     pub fn flush(&mut self) {
+        crate::profile_scope!("flush");
         self.batcher.flush(&mut self.device, &mut self.pipe);
     }
 
     pub fn next_quad_mut(&mut self, t: *mut Texture) -> &mut QuadData {
+        // flush here (instead of in the batcher) so that it's profiled
+        if self.batcher.is_satured() {
+            self.flush();
+        }
         self.batcher.next_quad_mut(t, &self.device, &mut self.pipe)
     }
+
+    fn next_push_mut(&mut self, tex: &impl Texture2d) -> QuadPush<'_> {
+        if self.batcher.is_satured() {
+            self.flush();
+        }
+
+        // we have to take care into ownership, unforunatelly
+        let target_quad =
+            self.batcher
+                .next_quad_mut(tex.raw_texture(), &self.device, &mut self.pipe);
+
+        QuadPush {
+            params: &mut self.push,
+            target: target_quad,
+        }
+    }
+
+    /// Sets the model-view matrix of the batcher. Flushes the batcher
+    pub fn set_transform(&mut self, mv: Mat4x4) {
+        self.flush();
+        self.batcher.set_transform(mv);
+    }
+
+    pub fn opacity(&self) -> f32 {
+        self.batcher.opacity()
+    }
+
+    /// Multiplies every color drawn after this call. Flushes the batcher
+    pub fn set_opacity(&mut self, opacity: f32) {
+        self.flush();
+        self.batcher.set_opacity(opacity);
+    }
 }
 
 /// Draw interface
@@ -103,14 +584,34 @@ impl DrawContext {
         BatchPass::new(self)
     }
 
+    /// Begins a batch pass transformed with the camera (world coordinates)
+    pub fn batch_with_camera(&mut self, camera: &Camera2d) -> BatchPass<'_> {
+        let mut pass = BatchPass::new(self);
+        pass.dcx.set_transform(camera.view_matrix());
+        pass
+    }
+
+    /// Begins a batch pass rendering to the render target
+    pub fn offscreen(&mut self, target: &RenderTarget2d) -> OffscreenPass<'_> {
+        OffscreenPass::new(self, target)
+    }
+
+    /// Begins a batch pass rendering to the render target, transformed with the camera
+    pub fn offscreen_with_camera(
+        &mut self,
+        target: &RenderTarget2d,
+        camera: &Camera2d,
+    ) -> OffscreenPass<'_> {
+        let mut pass = OffscreenPass::new(self, target);
+        pass.dcx.set_transform(camera.view_matrix());
+        pass
+    }
+
+    /// Logical screen size: the virtual resolution if any, or the backbuffer size (updated on
+    /// resize even before the next batch pass)
     pub fn screen(&self) -> Rect2f {
-        [
-            0.0,
-            0.0,
-            self.params.backBufferWidth as f32,
-            self.params.backBufferHeight as f32,
-        ]
-        .into()
+        let [w, h] = self.logical_size();
+        [0.0, 0.0, w as f32, h as f32].into()
     }
 
     pub fn dt(&self) -> Duration {
@@ -132,9 +633,13 @@ pub trait DrawApi {
 
     /// (Mainly) internal utilitiy to implement `linep and `rect`
     fn white_dot(&mut self) -> SpritePush {
-        unsafe { self.push(WHITE_DOT.as_ref().unwrap()) }
+        let white_dot = self.white_dot_texture().clone();
+        self.push(&white_dot)
     }
 
+    /// 1x1 white texture of the `DrawContext`
+    fn white_dot_texture(&self) -> &TextureData2d;
+
     fn line(&mut self, p1: impl Into<Vec2f>, p2: impl Into<Vec2f>, color: Color) {
         let p1 = p1.into();
         let p2 = p2.into();
@@ -174,50 +679,180 @@ pub struct BatchPass<'a> {
 /// Flush batch when it goes out of scope
 impl<'a> Drop for BatchPass<'a> {
     fn drop(&mut self) {
-        self.dcx
-            .batcher
-            .flush(&mut self.dcx.device, &mut self.dcx.pipe);
+        self.dcx.flush();
+        // reset the camera and the clip stack
+        self.dcx.batcher.set_transform(Mat4x4::identity());
+        self.dcx.clips.clear();
+        self.dcx.apply_clip();
+        self.dcx.draw_bars();
     }
 }
 
 impl<'a> BatchPass<'a> {
     pub fn new(dcx: &'a mut DrawContext) -> Self {
+        dcx.context.make_current();
+        dcx.sync_viewport();
         Self { dcx }
     }
+
+    /// Clips drawing to the rectangle (in screen coordinates) until it's popped. Nested clips
+    /// are intersected
+    pub fn push_clip(&mut self, rect: impl Into<Rect2f>) {
+        self.dcx.push_clip(rect);
+    }
+
+    pub fn pop_clip(&mut self) -> Option<Rect2f> {
+        self.dcx.pop_clip()
+    }
+
+    /// Replaces the clip stack with the scissor rectangle (or disables clipping with `None`)
+    pub fn set_scissor(&mut self, rect: Option<Rect2f>) {
+        self.dcx.set_scissor(rect);
+    }
+
+    pub fn clip_rect(&self) -> Option<&Rect2f> {
+        self.dcx.clip_rect()
+    }
 }
 
 impl<'a> DrawApi for BatchPass<'a> {
+    fn white_dot_texture(&self) -> &TextureData2d {
+        &self.dcx.white_dot
+    }
+
     fn next_quad_mut(&mut self, t: *mut Texture) -> &mut QuadData {
         self.dcx.next_quad_mut(t)
     }
 
     fn next_push_mut(&mut self, tex: &impl Texture2d) -> QuadPush<'_> {
-        // we have to take care into ownership, unforunatelly
-        let target_quad =
-            self.dcx
-                .batcher
-                .next_quad_mut(tex.raw_texture(), &self.dcx.device, &mut self.dcx.pipe);
-
-        QuadPush {
-            params: &mut self.dcx.push,
-            target: target_quad,
-        }
+        self.dcx.next_push_mut(tex)
     }
 }
 
-pub struct OffscreenPass<'a, 'b, S: OnSpritePush + Texture2d> {
+/// Handle to push sprites to a [`RenderTarget2d`]
+///
+/// The viewport and the projection matrix are switched to the render target, and restored when
+/// it goes out of scope. Passes can be nested:
+///
+/// ```no_run
+/// # use anf::{engine::draw::*, gfx::{Color, RenderTarget2d}};
+/// # fn f(dcx: &mut DrawContext, world: &RenderTarget2d, minimap: &RenderTarget2d) {
+/// let mut pass = dcx.offscreen(world);
+/// pass.clear(Color::rgba(0, 0, 0, 255));
+/// {
+///     let mut pass = pass.offscreen(minimap);
+///     // ~ draw the minimap
+/// }
+/// // ~ draw the world, then composite them onto the backbuffer with `dcx.batch()`
+/// # }
+/// ```
+pub struct OffscreenPass<'a> {
     dcx: &'a mut DrawContext,
-    target: fna3h::draw::pass::RenderTargetBinding,
-    sprite: &'b S,
-}
-
-// pub enum RenderPass<'a> {
-//     BackBuffer {
-//         dcx: &'a mut DrawContext,
-//         // scrissor, viewport
-//     },
-//     Offscreen{
-//         dcx: &'a mut DrawContext,
-//         target: fna3h::draw::pass::RenderTargetBinding,
-//     },
-// }
+}
+
+/// Flush batch and pop the render target when it goes out of scope
+impl<'a> Drop for OffscreenPass<'a> {
+    fn drop(&mut self) {
+        self.dcx.pop_render_target();
+    }
+}
+
+impl<'a> OffscreenPass<'a> {
+    pub fn new(dcx: &'a mut DrawContext, target: &RenderTarget2d) -> Self {
+        dcx.push_render_target(target);
+        Self { dcx }
+    }
+
+    /// Begins a nested pass. This pass is restored when the nested pass goes out of scope
+    pub fn offscreen(&mut self, target: &RenderTarget2d) -> OffscreenPass<'_> {
+        OffscreenPass::new(self.dcx, target)
+    }
+
+    /// Clears the render target
+    pub fn clear(&mut self, color: Color) {
+        self.dcx.flush();
+        ClearCommand::color(&self.dcx.device, color);
+    }
+
+    /// Clips drawing to the rectangle (in render target coordinates) until it's popped. Nested
+    /// clips are intersected
+    pub fn push_clip(&mut self, rect: impl Into<Rect2f>) {
+        self.dcx.push_clip(rect);
+    }
+
+    pub fn pop_clip(&mut self) -> Option<Rect2f> {
+        self.dcx.pop_clip()
+    }
+
+    /// Replaces the clip stack with the scissor rectangle (or disables clipping with `None`)
+    pub fn set_scissor(&mut self, rect: Option<Rect2f>) {
+        self.dcx.set_scissor(rect);
+    }
+
+    pub fn clip_rect(&self) -> Option<&Rect2f> {
+        self.dcx.clip_rect()
+    }
+}
+
+impl<'a> DrawApi for OffscreenPass<'a> {
+    fn white_dot_texture(&self) -> &TextureData2d {
+        &self.dcx.white_dot
+    }
+
+    fn next_quad_mut(&mut self, t: *mut Texture) -> &mut QuadData {
+        self.dcx.next_quad_mut(t)
+    }
+
+    fn next_push_mut(&mut self, tex: &impl Texture2d) -> QuadPush<'_> {
+        self.dcx.next_push_mut(tex)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn res(w: u32, h: u32, policy: ScalePolicy) -> VirtualResolution {
+        VirtualResolution {
+            policy,
+            ..VirtualResolution::new(w, h)
+        }
+    }
+
+    #[test]
+    fn test_stretch() {
+        let res = res(320, 180, ScalePolicy::Stretch);
+        assert_eq!(res.viewport([1000, 500]), [0, 0, 1000, 500]);
+    }
+
+    #[test]
+    fn test_fit_letterbox() {
+        let res = res(320, 180, ScalePolicy::Fit);
+        // bars on top and bottom
+        assert_eq!(res.viewport([1280, 800]), [0, 40, 1280, 720]);
+        // bars on left and right
+        assert_eq!(res.viewport([1600, 720]), [160, 0, 1280, 720]);
+    }
+
+    #[test]
+    fn test_integer() {
+        let res = res(320, 180, ScalePolicy::Integer);
+        assert_eq!(res.viewport([1000, 700]), [20, 80, 960, 540]);
+        assert_eq!(res.viewport([1280, 720]), [0, 0, 1280, 720]);
+    }
+
+    #[test]
+    fn test_smaller_window() {
+        // fit shrinks the screen
+        let fit = res(320, 180, ScalePolicy::Fit);
+        assert_eq!(fit.viewport([160, 90]), [0, 0, 160, 90]);
+
+        // integer scaling shows the logical size, overflowing the window
+        let integer = res(320, 180, ScalePolicy::Integer);
+        assert_eq!(integer.viewport([160, 90]), [-80, -45, 320, 180]);
+
+        // zero-sized logical screen fills the backbuffer
+        let zero = res(0, 0, ScalePolicy::Fit);
+        assert_eq!(zero.viewport([160, 90]), [0, 0, 160, 90]);
+    }
+}
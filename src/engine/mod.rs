@@ -8,8 +8,13 @@ context/user-data pattern lifecycle is run.
 [`anf_samples`]: https://github.com/toyboot4e/anf_samples
 !*/
 
+pub mod camera;
+#[cfg(feature = "capture")]
+pub mod capture;
 pub mod core;
 pub mod draw;
+pub mod profiler;
+pub mod scene;
 pub mod utils;
 
 mod embedded;
@@ -20,6 +25,7 @@ pub mod prelude {
 
     pub use crate::engine::{
         core::{
+            clock::{FrameTiming, GameClock, TargetFps},
             lifecycle::{AnfFramework, AnfLifecycle, AnfResult},
             window::{WindowConfig, WindowHandle},
         },
@@ -0,0 +1,352 @@
+/*! Per-stage frame profiler (enabled with `profiler` feature)
+
+The game loop times event pumping, each `update` call, `render` and batch flushes. Time your own
+code with [`profile_scope!`]:
+
+```no_run
+fn update_physics() {
+    anf::profile_scope!("physics");
+    // ..
+}
+```
+
+When `profiler` feature is disabled, [`profile_scope!`] expands to nothing.
+
+Record some frames and dump them as Chrome trace-event JSON (open it with `chrome://tracing`):
+
+```no_run
+# #[cfg(feature = "profiler")]
+anf::engine::profiler::with(|p| p.record(60));
+// ~ 60 frames later
+# #[cfg(feature = "profiler")]
+anf::engine::profiler::with(|p| p.write_chrome_trace("trace.json")).unwrap();
+```
+
+[`profile_scope!`]: crate::profile_scope
+!*/
+
+/// Times the rest of the block with the given `&'static str` name
+#[cfg(feature = "profiler")]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope_guard = $crate::engine::profiler::scope($name);
+    };
+}
+
+/// Times the rest of the block with the given `&'static str` name
+#[cfg(not(feature = "profiler"))]
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {};
+}
+
+#[cfg(feature = "profiler")]
+pub use self::imp::*;
+
+#[cfg(feature = "profiler")]
+mod imp {
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, VecDeque},
+        fmt::Write as _,
+        fs, io,
+        path::Path,
+        time::{Duration, Instant},
+    };
+
+    thread_local! {
+        static PROFILER: RefCell<Profiler> = RefCell::new(Profiler::new());
+    }
+
+    /// Accesses the profiler of the current thread
+    pub fn with<T>(f: impl FnOnce(&mut Profiler) -> T) -> T {
+        PROFILER.with(|p| f(&mut p.borrow_mut()))
+    }
+
+    /// Starts timing a scope. Prefer [`profile_scope!`](crate::profile_scope)
+    pub fn scope(name: &'static str) -> ScopeGuard {
+        ScopeGuard {
+            name,
+            start: Instant::now(),
+        }
+    }
+
+    /// Adds a sample to the profiler when it goes out of scope
+    #[derive(Debug)]
+    pub struct ScopeGuard {
+        name: &'static str,
+        start: Instant,
+    }
+
+    impl Drop for ScopeGuard {
+        fn drop(&mut self) {
+            let (name, start) = (self.name, self.start);
+            PROFILER.with(|p| {
+                // the profiler is already borrowed if the guard is dropped in `with`. The sample
+                // is dropped then
+                if let Ok(mut p) = p.try_borrow_mut() {
+                    p.add_sample(name, start, start.elapsed());
+                }
+            });
+        }
+    }
+
+    /// Rolling statistics of a scope
+    #[derive(Debug, Clone, Default)]
+    pub struct ScopeStats {
+        /// Number of samples since the profiler started
+        pub n_samples: u64,
+        /// Recent samples
+        samples: VecDeque<Duration>,
+    }
+
+    impl ScopeStats {
+        pub fn last(&self) -> Duration {
+            self.samples.back().cloned().unwrap_or_default()
+        }
+
+        pub fn avg(&self) -> Duration {
+            if self.samples.is_empty() {
+                return Duration::new(0, 0);
+            }
+            self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+        }
+
+        pub fn min(&self) -> Duration {
+            self.samples.iter().min().cloned().unwrap_or_default()
+        }
+
+        pub fn max(&self) -> Duration {
+            self.samples.iter().max().cloned().unwrap_or_default()
+        }
+    }
+
+    /// Chrome "complete" event
+    #[derive(Debug, Clone)]
+    struct TraceEvent {
+        name: &'static str,
+        frame: u64,
+        start: Duration,
+        dur: Duration,
+    }
+
+    #[derive(Debug)]
+    pub struct Profiler {
+        origin: Instant,
+        frame: u64,
+        frame_start: Option<Instant>,
+        /// Size of the rolling window
+        n_window: usize,
+        stats: HashMap<&'static str, ScopeStats>,
+        /// Number of frames to record from the next frame
+        n_pending_frames: u64,
+        /// Last frame to record (inclusive)
+        record_end: Option<u64>,
+        events: Vec<TraceEvent>,
+    }
+
+    impl Profiler {
+        fn new() -> Self {
+            Self {
+                origin: Instant::now(),
+                frame: 0,
+                frame_start: None,
+                n_window: 120,
+                stats: HashMap::new(),
+                n_pending_frames: 0,
+                record_end: None,
+                events: Vec::new(),
+            }
+        }
+
+        pub fn frame(&self) -> u64 {
+            self.frame
+        }
+
+        pub fn stats(&self, name: &str) -> Option<&ScopeStats> {
+            self.stats.get(name)
+        }
+
+        pub fn iter_stats(&self) -> impl Iterator<Item = (&&'static str, &ScopeStats)> {
+            self.stats.iter()
+        }
+
+        /// Sets the number of recent samples used for statistics (120 by default)
+        pub fn set_window(&mut self, n: usize) {
+            assert!(n > 0, "window must not be empty");
+            self.n_window = n;
+            for stats in self.stats.values_mut() {
+                while stats.samples.len() > n {
+                    stats.samples.pop_front();
+                }
+            }
+        }
+
+        /// Records trace events of the next `n_frames` frames, discarding the last recording
+        pub fn record(&mut self, n_frames: u64) {
+            self.events.clear();
+            self.n_pending_frames = n_frames;
+            self.record_end = None;
+        }
+
+        pub fn is_recording(&self) -> bool {
+            self.n_pending_frames > 0 || self.record_end.is_some()
+        }
+
+        /// Recorded events in Chrome trace-event format
+        pub fn chrome_trace_json(&self) -> String {
+            let mut json = String::from("{\"traceEvents\":[");
+            for (i, ev) in self.events.iter().enumerate() {
+                if i > 0 {
+                    json.push(',');
+                }
+                write!(
+                    json,
+                    "{{\"name\":\"{}\",\"cat\":\"anf\",\"ph\":\"X\",\"ts\":{},\"dur\":{},\"pid\":0,\"tid\":0,\"args\":{{\"frame\":{}}}}}",
+                    self::escape(ev.name),
+                    ev.start.as_micros(),
+                    ev.dur.as_micros(),
+                    ev.frame,
+                )
+                .unwrap();
+            }
+            json.push_str("]}");
+            json
+        }
+
+        pub fn write_chrome_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+            fs::write(path, self.chrome_trace_json())
+        }
+    }
+
+    /// Internals
+    impl Profiler {
+        pub(crate) fn begin_frame(&mut self) {
+            self.frame += 1;
+            self.frame_start = Some(Instant::now());
+
+            if self.n_pending_frames > 0 {
+                self.record_end = Some(self.frame + self.n_pending_frames - 1);
+                self.n_pending_frames = 0;
+            }
+        }
+
+        pub(crate) fn end_frame(&mut self) {
+            if let Some(start) = self.frame_start.take() {
+                self.add_sample("frame", start, start.elapsed());
+            }
+
+            if self.record_end == Some(self.frame) {
+                self.record_end = None;
+            }
+        }
+
+        fn add_sample(&mut self, name: &'static str, start: Instant, dur: Duration) {
+            let stats = self.stats.entry(name).or_default();
+            stats.n_samples += 1;
+            stats.samples.push_back(dur);
+            while stats.samples.len() > self.n_window {
+                stats.samples.pop_front();
+            }
+
+            if self.record_end.is_some() {
+                self.events.push(TraceEvent {
+                    name,
+                    frame: self.frame,
+                    start: start.saturating_duration_since(self.origin),
+                    dur,
+                });
+            }
+        }
+    }
+
+    fn escape(s: &str) -> String {
+        s.replace('\\', "\\\\").replace('"', "\\\"")
+    }
+
+    #[cfg(test)]
+    mod test {
+        use super::*;
+
+        fn run_frame(p: &mut Profiler) {
+            p.begin_frame();
+            let start = Instant::now();
+            p.add_sample("a", start, Duration::from_millis(1));
+            p.end_frame();
+        }
+
+        #[test]
+        fn test_record_frames() {
+            let mut p = Profiler::new();
+            run_frame(&mut p);
+            assert!(p.events.is_empty());
+
+            p.record(2);
+            assert!(p.is_recording());
+            run_frame(&mut p);
+            run_frame(&mut p);
+            assert!(!p.is_recording());
+            run_frame(&mut p);
+
+            // `a` and `frame` of frames 2 and 3
+            let frames = p.events.iter().map(|ev| ev.frame).collect::<Vec<_>>();
+            assert_eq!(frames, vec![2, 2, 3, 3]);
+        }
+
+        #[test]
+        fn test_window() {
+            let mut p = Profiler::new();
+            p.set_window(3);
+            let start = Instant::now();
+            for i in 1..=5 {
+                p.add_sample("a", start, Duration::from_millis(i));
+            }
+
+            let stats = p.stats("a").unwrap();
+            assert_eq!(stats.n_samples, 5);
+            assert_eq!(stats.last(), Duration::from_millis(5));
+            assert_eq!(stats.min(), Duration::from_millis(3));
+            assert_eq!(stats.max(), Duration::from_millis(5));
+            assert_eq!(stats.avg(), Duration::from_millis(4));
+        }
+
+        #[test]
+        fn test_chrome_trace_json() {
+            let mut p = Profiler::new();
+            assert_eq!(p.chrome_trace_json(), "{\"traceEvents\":[]}");
+
+            p.record(1);
+            p.begin_frame();
+            let origin = p.origin;
+            p.add_sample(
+                "a\"b",
+                origin + Duration::from_millis(1),
+                Duration::from_millis(2),
+            );
+            p.add_sample(
+                "c",
+                origin + Duration::from_millis(3),
+                Duration::from_millis(4),
+            );
+
+            assert_eq!(
+                p.chrome_trace_json(),
+                concat!(
+                    "{\"traceEvents\":[",
+                    "{\"name\":\"a\\\"b\",\"cat\":\"anf\",\"ph\":\"X\",\"ts\":1000,\"dur\":2000,\"pid\":0,\"tid\":0,\"args\":{\"frame\":1}},",
+                    "{\"name\":\"c\",\"cat\":\"anf\",\"ph\":\"X\",\"ts\":3000,\"dur\":4000,\"pid\":0,\"tid\":0,\"args\":{\"frame\":1}}",
+                    "]}"
+                )
+            );
+        }
+
+        #[test]
+        fn test_guard_in_with() {
+            with(|_p| {
+                let _guard = scope("nested");
+            });
+            assert!(with(|p| p.stats("nested").is_none()));
+        }
+    }
+}
@@ -0,0 +1,631 @@
+/*! Scene stack
+
+[`SceneStack`] is driven by your [`AnfLifecycle`] implementation:
+
+```no_run
+use {anf::engine::{prelude::*, scene::*}, sdl2::event::Event, std::time::Duration};
+
+struct Game {
+    dcx: DrawContext,
+    scenes: SceneStack,
+}
+
+impl AnfLifecycle for Game {
+    fn event(&mut self, ev: &Event) -> AnfResult<()> {
+        self.scenes.event(ev)
+    }
+
+    fn update(&mut self, dt: Duration) -> AnfResult<()> {
+        self.scenes.update(dt)
+    }
+
+    fn render(&mut self, dt: Duration, alpha: f32) -> AnfResult<()> {
+        self.scenes.render(&mut self.dcx, dt, alpha)
+    }
+
+    fn should_quit(&self) -> bool {
+        self.scenes.is_empty()
+    }
+}
+```
+
+[`AnfLifecycle`]: crate::engine::core::lifecycle::AnfLifecycle
+!*/
+
+use {
+    fna3h::Color,
+    sdl2::event::Event,
+    std::{collections::VecDeque, time::Duration},
+};
+
+use crate::{
+    engine::{core::lifecycle::AnfResult, draw::*},
+    gfx::ClearCommand,
+};
+
+/// One state of the game, such as title, field or pause menu
+///
+/// Scenes don't have to clear the screen; [`SceneStack`] does it.
+pub trait Scene {
+    /// Called when the scene is pushed onto the stack
+    fn on_enter(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Called when the scene is removed from the stack
+    fn on_exit(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Called when another scene is pushed on top of this scene
+    fn on_pause(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Called when this scene becomes the top of the stack again
+    fn on_resume(&mut self) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Only the top scene receives events
+    #[allow(unused_variables)]
+    fn event(&mut self, ev: &Event) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// Returns a command to modify the [`SceneStack`]
+    #[allow(unused_variables)]
+    fn update(&mut self, dt: Duration) -> AnfResult<SceneCommand> {
+        Ok(SceneCommand::None)
+    }
+
+    #[allow(unused_variables)]
+    fn render(&mut self, dcx: &mut DrawContext, dt: Duration, alpha: f32) -> AnfResult<()> {
+        Ok(())
+    }
+
+    /// How scenes below this scene are handled
+    fn options(&self) -> SceneOptions {
+        SceneOptions::default()
+    }
+}
+
+/// How scenes below a scene are handled. Both are `false` by default
+///
+/// A pause menu would render the scene below it but wouldn't update it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SceneOptions {
+    pub updates_below: bool,
+    pub renders_below: bool,
+}
+
+/// Modification to [`SceneStack`] returned by [`Scene::update`]
+pub enum SceneCommand {
+    None,
+    Push(Box<dyn Scene>, Transition),
+    Pop(Transition),
+    Replace(Box<dyn Scene>, Transition),
+}
+
+/// Timed effect on [`SceneCommand`], advanced by `dt` of `update`s
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transition {
+    None,
+    /// Fades out to the color and then fades in to the new scene
+    Fade {
+        color: Color,
+        duration: Duration,
+    },
+    /// Blends the old scene into the new scene
+    ///
+    /// The new scene is drawn with [`DrawContext::set_opacity`] on top of the old scene.
+    Crossfade {
+        duration: Duration,
+    },
+}
+
+/// Stack of [`Scene`]s. The top scene is active
+pub struct SceneStack {
+    scenes: Vec<Box<dyn Scene>>,
+    /// Commands waiting for the current transition to finish
+    queue: VecDeque<SceneCommand>,
+    transition: Option<ActiveTransition>,
+    /// Color to clear the screen with before rendering scenes
+    pub clear_color: Option<Color>,
+}
+
+struct ActiveTransition {
+    kind: Transition,
+    elapsed: Duration,
+    /// [Fade] Command applied in the middle of the transition
+    cmd: Option<SceneCommand>,
+    /// [Crossfade] Scene removed from the stack, rendered until the transition ends
+    outgoing: Option<Box<dyn Scene>>,
+}
+
+impl ActiveTransition {
+    fn duration(&self) -> Duration {
+        match self.kind {
+            Transition::None => Duration::new(0, 0),
+            Transition::Fade { duration, .. } | Transition::Crossfade { duration } => duration,
+        }
+    }
+
+    /// Normalized progress in range `[0.0, 1.0]`
+    fn progress(&self) -> f32 {
+        let duration = self.duration();
+        if duration == Duration::new(0, 0) {
+            1.0
+        } else {
+            (self.elapsed.as_secs_f32() / duration.as_secs_f32()).min(1.0)
+        }
+    }
+}
+
+impl Default for SceneStack {
+    fn default() -> Self {
+        Self {
+            scenes: Vec::new(),
+            queue: VecDeque::new(),
+            transition: None,
+            clear_color: Some(Color::rgba(0, 0, 0, 255)),
+        }
+    }
+}
+
+impl SceneStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a stack with an initial scene
+    pub fn with_scene(mut scene: Box<dyn Scene>) -> AnfResult<Self> {
+        let mut stack = Self::new();
+        scene.on_enter()?;
+        stack.scenes.push(scene);
+        Ok(stack)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scenes.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.scenes.len()
+    }
+
+    pub fn is_transitioning(&self) -> bool {
+        self.transition.is_some()
+    }
+
+    pub fn push(&mut self, scene: Box<dyn Scene>, trans: Transition) -> AnfResult<()> {
+        self.command(SceneCommand::Push(scene, trans))
+    }
+
+    pub fn pop(&mut self, trans: Transition) -> AnfResult<()> {
+        self.command(SceneCommand::Pop(trans))
+    }
+
+    pub fn replace(&mut self, scene: Box<dyn Scene>, trans: Transition) -> AnfResult<()> {
+        self.command(SceneCommand::Replace(scene, trans))
+    }
+
+    /// Runs the command, or queues it if any transition is running
+    pub fn command(&mut self, cmd: SceneCommand) -> AnfResult<()> {
+        if let SceneCommand::None = cmd {
+            return Ok(());
+        }
+
+        if self.transition.is_some() {
+            self.queue.push_back(cmd);
+            return Ok(());
+        }
+
+        self.start(cmd)
+    }
+}
+
+/// Lifecycle
+impl SceneStack {
+    pub fn event(&mut self, ev: &Event) -> AnfResult<()> {
+        if let Some(top) = self.scenes.last_mut() {
+            top.event(ev)?;
+        }
+        Ok(())
+    }
+
+    pub fn update(&mut self, dt: Duration) -> AnfResult<()> {
+        self.update_transition(dt)?;
+
+        // update from the top, collecting commands
+        let mut cmds = Vec::new();
+        for scene in self.scenes.iter_mut().rev() {
+            cmds.push(scene.update(dt)?);
+            if !scene.options().updates_below {
+                break;
+            }
+        }
+
+        for cmd in cmds {
+            self.command(cmd)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn render(&mut self, dcx: &mut DrawContext, dt: Duration, alpha: f32) -> AnfResult<()> {
+        if let Some(color) = self.clear_color {
+            ClearCommand::color(dcx.device(), color);
+        }
+
+        let trans = match self.transition.as_mut() {
+            Some(trans) => trans,
+            None => return Self::render_scenes(&mut self.scenes, dcx, dt, alpha),
+        };
+
+        let t = trans.progress();
+        match trans.kind {
+            Transition::None => Self::render_scenes(&mut self.scenes, dcx, dt, alpha),
+            Transition::Fade { color, .. } => {
+                Self::render_scenes(&mut self.scenes, dcx, dt, alpha)?;
+                // fade out in the first half and fade in in the second half
+                let overlay = if t < 0.5 { t * 2.0 } else { (1.0 - t) * 2.0 };
+                self::fill_screen(dcx, color, overlay);
+                Ok(())
+            }
+            Transition::Crossfade { .. } => {
+                let opacity = dcx.opacity();
+
+                if let Some(outgoing) = trans.outgoing.as_mut() {
+                    // popped or replaced
+                    outgoing.render(dcx, dt, alpha)?;
+                    dcx.set_opacity(opacity * t);
+                    Self::render_scenes(&mut self.scenes, dcx, dt, alpha)?;
+                } else if !self.scenes.is_empty() {
+                    // pushed
+                    let n = self.scenes.len();
+                    let (below, top) = self.scenes.split_at_mut(n - 1);
+                    Self::render_scenes(below, dcx, dt, alpha)?;
+                    dcx.set_opacity(opacity * t);
+                    top[0].render(dcx, dt, alpha)?;
+                }
+
+                dcx.set_opacity(opacity);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Internals
+impl SceneStack {
+    /// Renders visible scenes from bottom to top
+    fn render_scenes(
+        scenes: &mut [Box<dyn Scene>],
+        dcx: &mut DrawContext,
+        dt: Duration,
+        alpha: f32,
+    ) -> AnfResult<()> {
+        let mut lo = scenes.len();
+        for scene in scenes.iter().rev() {
+            lo -= 1;
+            if !scene.options().renders_below {
+                break;
+            }
+        }
+
+        for scene in scenes[lo..].iter_mut() {
+            scene.render(dcx, dt, alpha)?;
+        }
+
+        Ok(())
+    }
+
+    fn start(&mut self, cmd: SceneCommand) -> AnfResult<()> {
+        let trans = match &cmd {
+            SceneCommand::None => return Ok(()),
+            SceneCommand::Push(_, trans)
+            | SceneCommand::Pop(trans)
+            | SceneCommand::Replace(_, trans) => *trans,
+        };
+
+        match trans {
+            Transition::None => {
+                self.apply(cmd)?;
+            }
+            Transition::Fade { .. } => {
+                self.transition = Some(ActiveTransition {
+                    kind: trans,
+                    elapsed: Duration::new(0, 0),
+                    cmd: Some(cmd),
+                    outgoing: None,
+                });
+            }
+            Transition::Crossfade { .. } => {
+                let outgoing = self.apply(cmd)?;
+                self.transition = Some(ActiveTransition {
+                    kind: trans,
+                    elapsed: Duration::new(0, 0),
+                    cmd: None,
+                    outgoing,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn update_transition(&mut self, dt: Duration) -> AnfResult<()> {
+        let trans = match self.transition.as_mut() {
+            Some(trans) => trans,
+            None => return Ok(()),
+        };
+
+        trans.elapsed += dt;
+        let t = trans.progress();
+
+        // [Fade] apply the command in the middle of the transition
+        if t >= 0.5 {
+            if let Some(cmd) = trans.cmd.take() {
+                self.apply(cmd)?;
+            }
+        }
+
+        if t >= 1.0 {
+            if let Some(mut trans) = self.transition.take() {
+                if let Some(mut outgoing) = trans.outgoing.take() {
+                    outgoing.on_exit()?;
+                }
+            }
+
+            if let Some(cmd) = self.queue.pop_front() {
+                self.start(cmd)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Modifies the stack. Returns the removed scene (on pop or replace) without calling `on_exit`
+    /// if the command is for crossfade
+    fn apply(&mut self, cmd: SceneCommand) -> AnfResult<Option<Box<dyn Scene>>> {
+        let is_crossfade = |trans: &Transition| matches!(trans, Transition::Crossfade { .. });
+
+        match cmd {
+            SceneCommand::None => Ok(None),
+            SceneCommand::Push(mut scene, _) => {
+                if let Some(top) = self.scenes.last_mut() {
+                    top.on_pause()?;
+                }
+                scene.on_enter()?;
+                self.scenes.push(scene);
+                Ok(None)
+            }
+            SceneCommand::Pop(trans) => {
+                let removed = self.remove_top(is_crossfade(&trans))?;
+                if let Some(top) = self.scenes.last_mut() {
+                    top.on_resume()?;
+                }
+                Ok(removed)
+            }
+            SceneCommand::Replace(mut scene, trans) => {
+                let removed = self.remove_top(is_crossfade(&trans))?;
+                scene.on_enter()?;
+                self.scenes.push(scene);
+                Ok(removed)
+            }
+        }
+    }
+
+    fn remove_top(&mut self, keep: bool) -> AnfResult<Option<Box<dyn Scene>>> {
+        let mut scene = match self.scenes.pop() {
+            Some(scene) => scene,
+            None => return Ok(None),
+        };
+
+        if keep {
+            Ok(Some(scene))
+        } else {
+            scene.on_exit()?;
+            Ok(None)
+        }
+    }
+}
+
+/// Fills the screen with the color multiplied by `alpha`
+fn fill_screen(dcx: &mut DrawContext, color: Color, alpha: f32) {
+    let screen = dcx.screen();
+    let color = anf_gfx::batcher::premultiply(color, alpha);
+
+    let mut pass = dcx.batch();
+    pass.white_dot().color(color).dest_rect_px(screen);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{cell::RefCell, rc::Rc};
+
+    type Log = Rc<RefCell<Vec<String>>>;
+
+    /// Scene that logs its lifecycle
+    struct Logged {
+        name: &'static str,
+        log: Log,
+        options: SceneOptions,
+    }
+
+    impl Scene for Logged {
+        fn on_enter(&mut self) -> AnfResult<()> {
+            self.log("enter");
+            Ok(())
+        }
+
+        fn on_exit(&mut self) -> AnfResult<()> {
+            self.log("exit");
+            Ok(())
+        }
+
+        fn on_pause(&mut self) -> AnfResult<()> {
+            self.log("pause");
+            Ok(())
+        }
+
+        fn on_resume(&mut self) -> AnfResult<()> {
+            self.log("resume");
+            Ok(())
+        }
+
+        fn update(&mut self, _dt: Duration) -> AnfResult<SceneCommand> {
+            self.log("update");
+            Ok(SceneCommand::None)
+        }
+
+        fn options(&self) -> SceneOptions {
+            self.options
+        }
+    }
+
+    impl Logged {
+        fn log(&self, hook: &str) {
+            self.log
+                .borrow_mut()
+                .push(format!("{} {}", self.name, hook));
+        }
+    }
+
+    fn scene(name: &'static str, log: &Log) -> Box<dyn Scene> {
+        self::scene_with(name, log, SceneOptions::default())
+    }
+
+    fn scene_with(name: &'static str, log: &Log, options: SceneOptions) -> Box<dyn Scene> {
+        Box::new(Logged {
+            name,
+            log: Rc::clone(log),
+            options,
+        })
+    }
+
+    /// Takes the log
+    fn take(log: &Log) -> Vec<String> {
+        std::mem::take(&mut *log.borrow_mut())
+    }
+
+    #[test]
+    fn test_hook_order() {
+        let log = Log::default();
+        let mut stack = SceneStack::with_scene(scene("a", &log)).unwrap();
+        assert_eq!(take(&log), vec!["a enter"]);
+
+        stack.push(scene("b", &log), Transition::None).unwrap();
+        assert_eq!(take(&log), vec!["a pause", "b enter"]);
+
+        stack.pop(Transition::None).unwrap();
+        assert_eq!(take(&log), vec!["b exit", "a resume"]);
+
+        stack.replace(scene("c", &log), Transition::None).unwrap();
+        assert_eq!(take(&log), vec!["a exit", "c enter"]);
+        assert_eq!(stack.len(), 1);
+    }
+
+    #[test]
+    fn test_updates_below() {
+        let log = Log::default();
+        let updates_below = SceneOptions {
+            updates_below: true,
+            renders_below: false,
+        };
+
+        let mut stack = SceneStack::with_scene(scene("a", &log)).unwrap();
+        stack
+            .push(scene_with("b", &log, updates_below), Transition::None)
+            .unwrap();
+        take(&log);
+
+        stack.update(Duration::from_millis(16)).unwrap();
+        assert_eq!(take(&log), vec!["b update", "a update"]);
+
+        stack.push(scene("c", &log), Transition::None).unwrap();
+        take(&log);
+
+        stack.update(Duration::from_millis(16)).unwrap();
+        assert_eq!(take(&log), vec!["c update"]);
+    }
+
+    #[test]
+    fn test_fade() {
+        let log = Log::default();
+        let fade = Transition::Fade {
+            color: Color::rgba(0, 0, 0, 255),
+            duration: Duration::from_millis(100),
+        };
+
+        let mut stack = SceneStack::with_scene(scene("a", &log)).unwrap();
+        stack.push(scene("b", &log), fade).unwrap();
+        take(&log);
+
+        stack.update(Duration::from_millis(40)).unwrap();
+        assert_eq!(stack.len(), 1);
+
+        // applied in the middle of the transition
+        stack.update(Duration::from_millis(10)).unwrap();
+        assert_eq!(stack.len(), 2);
+        assert_eq!(
+            take(&log),
+            vec!["a update", "a pause", "b enter", "b update"]
+        );
+        assert!(stack.is_transitioning());
+
+        stack.update(Duration::from_millis(50)).unwrap();
+        assert!(!stack.is_transitioning());
+    }
+
+    #[test]
+    fn test_crossfade_exit() {
+        let log = Log::default();
+        let crossfade = Transition::Crossfade {
+            duration: Duration::from_millis(100),
+        };
+
+        let mut stack = SceneStack::with_scene(scene("a", &log)).unwrap();
+        stack.push(scene("b", &log), Transition::None).unwrap();
+        take(&log);
+
+        // the outgoing scene is removed immediately but exits at the end
+        stack.pop(crossfade).unwrap();
+        assert_eq!(stack.len(), 1);
+        assert_eq!(take(&log), vec!["a resume"]);
+
+        stack.update(Duration::from_millis(50)).unwrap();
+        assert_eq!(take(&log), vec!["a update"]);
+
+        stack.update(Duration::from_millis(50)).unwrap();
+        assert_eq!(take(&log), vec!["b exit", "a update"]);
+        assert!(!stack.is_transitioning());
+    }
+
+    #[test]
+    fn test_queue_during_transition() {
+        let log = Log::default();
+        let fade = Transition::Fade {
+            color: Color::rgba(0, 0, 0, 255),
+            duration: Duration::from_millis(100),
+        };
+
+        let mut stack = SceneStack::with_scene(scene("a", &log)).unwrap();
+        stack.push(scene("b", &log), fade).unwrap();
+        stack.push(scene("c", &log), Transition::None).unwrap();
+        assert_eq!(stack.len(), 1);
+        take(&log);
+
+        // the fade ends and then the queued command runs
+        stack.update(Duration::from_millis(100)).unwrap();
+        assert_eq!(stack.len(), 3);
+        assert_eq!(
+            take(&log),
+            vec!["a pause", "b enter", "b pause", "c enter", "c update"]
+        );
+        assert!(!stack.is_transitioning());
+    }
+}
@@ -1,6 +1,15 @@
 //! Framework utilities
 
-use ::std::time::Duration;
+use ::std::{
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[cfg(feature = "input")]
+use {
+    crate::engine::{core::clock::GameClock, draw::DrawContext},
+    xdl::Key,
+};
 
 /// Frame rate counter
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
@@ -25,3 +34,103 @@ impl FpsCounter {
         }
     }
 }
+
+/// Debug key bindings to control [`GameClock`]
+///
+/// Call [`DebugClockKeys::apply`] in `AnfLifecycle::on_tick`.
+#[cfg(feature = "input")]
+#[derive(Debug, Clone)]
+pub struct DebugClockKeys {
+    /// Toggles pause
+    pub pause: Key,
+    /// Runs exactly one `update` (and pauses the clock)
+    pub step: Key,
+    /// Halves the time scale (down to [`DebugClockKeys::MIN_TIME_SCALE`])
+    pub slower: Key,
+    /// Doubles the time scale (up to [`DebugClockKeys::MAX_TIME_SCALE`])
+    pub faster: Key,
+}
+
+#[cfg(feature = "input")]
+impl Default for DebugClockKeys {
+    fn default() -> Self {
+        Self {
+            pause: Key::F5,
+            step: Key::F6,
+            slower: Key::F7,
+            faster: Key::F8,
+        }
+    }
+}
+
+#[cfg(feature = "input")]
+impl DebugClockKeys {
+    pub const MIN_TIME_SCALE: f64 = 1.0 / 16.0;
+    pub const MAX_TIME_SCALE: f64 = 16.0;
+
+    pub fn apply(&self, kbd: &xdl::Keyboard, clock: &mut GameClock) {
+        if kbd.is_key_pressed(self.pause) {
+            clock.toggle_pause();
+        }
+        if kbd.is_key_pressed(self.step) {
+            clock.step();
+        }
+        if kbd.is_key_pressed(self.slower) {
+            Self::scale_time(clock, 0.5);
+        }
+        if kbd.is_key_pressed(self.faster) {
+            Self::scale_time(clock, 2.0);
+        }
+    }
+
+    /// Multiplies the time scale, clamping it so that it never freezes or overflows the clock
+    fn scale_time(clock: &mut GameClock, factor: f64) {
+        let scale = (clock.time_scale() * factor)
+            .max(Self::MIN_TIME_SCALE)
+            .min(Self::MAX_TIME_SCALE);
+        clock.set_time_scale(scale);
+    }
+}
+
+/// Path to a screenshot file named with the current time in milliseconds
+pub fn screenshot_path(dir: impl Into<PathBuf>) -> PathBuf {
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    dir.into().join(format!("screenshot_{}.png", millis))
+}
+
+/// Key binding to save screenshots
+///
+/// Call [`ScreenshotKey::apply`] every frame. The screenshot is saved when the frame is presented.
+#[cfg(feature = "input")]
+#[derive(Debug, Clone)]
+pub struct ScreenshotKey {
+    pub key: Key,
+    /// Directory to save screenshots
+    pub dir: PathBuf,
+}
+
+#[cfg(feature = "input")]
+impl Default for ScreenshotKey {
+    fn default() -> Self {
+        Self {
+            key: Key::F12,
+            dir: PathBuf::from("screenshots"),
+        }
+    }
+}
+
+#[cfg(feature = "input")]
+impl ScreenshotKey {
+    /// Returns the path of the requested screenshot
+    pub fn apply(&self, kbd: &xdl::Keyboard, dcx: &mut DrawContext) -> Option<PathBuf> {
+        if !kbd.is_key_pressed(self.key) {
+            return None;
+        }
+        let path = self::screenshot_path(&self.dir);
+        dcx.request_screenshot(path.clone());
+        Some(path)
+    }
+}
@@ -11,8 +11,11 @@ These structs implement draw API traits in [`crate::engine::draw`] so they can b
 
 pub use {
     anf_gfx::{
+        camera::Camera2d,
         geom2d, geom3d,
-        texture::{SpriteData, SubTextureData2d, Texture2dDrop, TextureData2d},
+        texture::{
+            encode_png, RenderTarget2d, SpriteData, SubTextureData2d, Texture2dDrop, TextureData2d,
+        },
     },
     fna3h::{draw::pass::ClearOptions, Color, Device, Vec4},
 };
@@ -23,8 +26,9 @@ pub use fna3d_fontstash as font;
 pub mod prelude {
     //! All of the 2D graphics data types (not 3D)
     pub use anf_gfx::{
+        camera::Camera2d,
         geom2d::*,
-        texture::{SpriteData, SubTextureData2d, Texture2dDrop, TextureData2d},
+        texture::{RenderTarget2d, SpriteData, SubTextureData2d, Texture2dDrop, TextureData2d},
     };
 
     pub use fna3h::Color;
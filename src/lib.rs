@@ -10,7 +10,7 @@ have to do it yourself.
 
 # Restrictions
 
-* One-window, single-threaded
+* Single-threaded (multiple windows are supported via `WindowHandle::open_window`)
 * Pre-defined game loop
 
 FIXME: `cargo test` fails in ANF (so using `no_run` for now)